@@ -22,8 +22,9 @@ use runtime_primitives::{
 };
 use client::{
 	block_builder::api::{CheckInherentsResult, InherentData, self as block_builder_api},
-	runtime_api, impl_runtime_apis
+	runtime_api, impl_runtime_apis, decl_runtime_apis
 };
+use parity_codec::Codec;
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
 use version::NativeVersion;
@@ -50,8 +51,29 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
+/// Balance of an account.
+pub type Balance = u128;
+
 // Declare the use of substratekitties module
-mod substratekitties;
+pub mod substratekitties;
+
+decl_runtime_apis! {
+	/// The API to query kitties without issuing one storage query per kitty.
+	#[api_version(3)]
+	pub trait KittiesApi<AccountId, Hash, Kitty, KittyInfo> where
+		AccountId: Codec,
+		Hash: Codec,
+		Kitty: Codec,
+		KittyInfo: Codec,
+	{
+		/// Returns a page of at most `limit` kitties owned by `account`, starting at its `start`-th kitty.
+		fn owned_kitties(account: AccountId, start: u64, limit: u32) -> Vec<(Hash, Kitty)>;
+		/// Returns the kitty together with its owner and indexes, or `None` if it does not exist.
+		fn get_kitty(id: Hash) -> Option<KittyInfo>;
+		/// Returns the raw DNA bytes of the kitty, or `None` if it does not exist.
+		fn dna_bytes(id: Hash) -> Option<Vec<u8>>;
+	}
+}
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -157,9 +179,9 @@ impl timestamp::Trait for Runtime {
 
 impl balances::Trait for Runtime {
 	/// The type for recording an account's balance.
-	type Balance = u128;
+	type Balance = Balance;
 	/// What to do if an account's free balance gets zeroed.
-	type OnFreeBalanceZero = ();
+	type OnFreeBalanceZero = Substratekitties;
 	/// What to do if a new account is created.
 	type OnNewAccount = Indices;
 	/// Restrict whether an account can transfer funds. We don't place any further restrictions.
@@ -177,6 +199,18 @@ impl sudo::Trait for Runtime {
 // Implementation of substratekitties module
 impl substratekitties::Trait for Runtime {
 	type Event = Event;
+	/// Derive kitty ids and DNA from the block's random seed.
+	type Randomness = substratekitties::SystemRandomness<Runtime>;
+	/// Keep the global "kitty #N of all kitties" enumeration.
+	const ENUMERATE_ALL_KITTIES: bool = true;
+	/// Genomes are as long as a hash for now, but no longer tied to it.
+	const GENOME_LENGTH: u32 = 32;
+	/// Transfers are free; set a fee here to fund the treasury.
+	const TRANSFER_FEE: u64 = 0;
+	/// Gen-0 kitties can be created without limit; bred kitties are never capped.
+	const GEN0_CAP: u64 = 0;
+	/// No other module needs to hear about kitty transfers yet.
+	type OnKittyTransfer = ();
 }
 
 construct_runtime!(
@@ -194,7 +228,7 @@ construct_runtime!(
 		Sudo: sudo,
 
         // Add my first substratekitties module.
-        Substratekitties: substratekitties::{Module, Call, Storage, Event<T>},
+        Substratekitties: substratekitties::{Module, Call, Storage, Config<T>, Event<T>},
 	}
 );
 
@@ -274,4 +308,24 @@ impl_runtime_apis! {
 			Aura::slot_duration()
 		}
 	}
+
+	impl self::KittiesApi<
+		Block,
+		AccountId,
+		Hash,
+		substratekitties::Kitty<Hash, Balance>,
+		substratekitties::KittyInfo<AccountId, Hash, Balance>,
+	> for Runtime {
+		fn owned_kitties(account: AccountId, start: u64, limit: u32) -> Vec<(Hash, substratekitties::Kitty<Hash, Balance>)> {
+			Substratekitties::owned_kitties(account, start, limit)
+		}
+
+		fn get_kitty(id: Hash) -> Option<substratekitties::KittyInfo<AccountId, Hash, Balance>> {
+			Substratekitties::get_kitty(id)
+		}
+
+		fn dna_bytes(id: Hash) -> Option<Vec<u8>> {
+			Substratekitties::dna_bytes(id)
+		}
+	}
 }
@@ -1,8 +1,9 @@
-use parity_codec::Encode;
-use system::ensure_signed;
-use support::{decl_storage, decl_module, StorageValue, StorageMap, dispatch::Result, ensure, decl_event};
-use runtime_primitives::traits::{As, Hash, Zero};
-use rstd::cmp;
+use parity_codec::{Encode, Decode};
+use system::{ensure_signed, ensure_root};
+use support::{decl_storage, decl_module, StorageValue, StorageMap, StorageDoubleMap, dispatch::Result, ensure, decl_event};
+use runtime_primitives::traits::{As, Hash, Zero, Saturating, CheckedAdd, CheckedSub, CheckedMul, OnFreeBalanceZero, EnsureAccountLiquid};
+use rstd::{cmp, result, marker::PhantomData};
+use rstd::prelude::*;
 
 // Substrateでは「あるトランザクションがFinalizeされたことが、直接そのトランザクションによって実行される
 // 関数が成功裏に終わったこと」を意味しない。Substrateでは「呼び出された関数が成功裏に終わったこと」を
@@ -28,31 +29,492 @@ use rstd::cmp;
 // 「データをブロックチェーンから引き出して、更新する」という操作はverify first, write lastの原則を
 // 適用することが求められる。
 
-// kittyの所有権の変更はSwap and Popメソッドで行う。
+// 所有者ごとのkittyは`(所有者, kitty)`をキーとする双方向連結リストで持ち、所有権の変更ではkittyを
+// 転送元のリストから外して転送先のリストの末尾につなぐ。他のkittyの順番は変わらない。
+// 全kittyのリストはSwap and Popメソッドで詰める。
 
 pub trait Trait: balances::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+
+    // 全kittyの通し番号（`AllKittiesArray`と`AllKittiesIndex`）を管理するか。
+    // falseにするとmintと削除でこれらを書かず、`AllKittiesCount`だけを数える。
+    // ストレージの移行は通し番号を使うので、途中で切り替えずチェーンの開始時に決めること。
+    const ENUMERATE_ALL_KITTIES: bool;
+
+    // kittyのidやDNAに使う乱数の出どころ。チェーンでは`SystemRandomness`を使い、テストでは決まった値を返す実装に差し替える。
+    type Randomness: Randomness<Self::Hash>;
+
+    // 遺伝子（`Genomes`）のバイト数。ハッシュの幅とは独立に決められる。
+    // 変えても既存のkittyの遺伝子はそのままで、交配では親の遺伝子を繰り返して新しい長さに合わせる。
+    const GENOME_LENGTH: u32;
+
+    // `transfer`でkittyを1匹転送するたびに送り手が`Treasury`へ払う手数料。0なら取らない。
+    // 代金が動く`buy_kitty`などには掛からない。
+    const TRANSFER_FEE: u64;
+
+    // `create_kitty`で生成できる第0世代のkittyの総数の上限。0なら制限しない。交配で生まれるkittyは数えない。
+    const GEN0_CAP: u64;
+
+    // kittyの所有者が変わったとき（生成・転送・削除）に呼ばれるフック。何もしないなら`()`を使う。
+    type OnKittyTransfer: OnKittyTransfer<Self::AccountId, Self::Hash>;
+}
+
+// kittyの所有者が変わったことを他のモジュールに知らせる。
+// 状態を書き終えてからイベントの前に呼ばれ、失敗を返せないので転送を取り消すことはできない。
+pub trait OnKittyTransfer<AccountId, Hash> {
+    // `kitty_id`が`to`のもとに生まれた。
+    fn on_mint(_to: &AccountId, _kitty_id: &Hash) {}
+    // `kitty_id`が`from`から`to`へ移った。
+    fn on_transfer(_from: &AccountId, _to: &AccountId, _kitty_id: &Hash) {}
+    // `owner`の`kitty_id`が削除された。
+    fn on_burn(_owner: &AccountId, _kitty_id: &Hash) {}
+}
+
+impl<AccountId, Hash> OnKittyTransfer<AccountId, Hash> for () {}
+
+// 乱数を返す。`subject`は用途ごとに異なる乱数を得るための値である。
+pub trait Randomness<Output> {
+    fn random(subject: &[u8]) -> Output;
+}
+
+// 代替不可能なトークンを持ったり動かしたりする操作。
+// ゲームやガバナンスなど他のモジュールは、extrinsicを経由せずにこのトレイトでkittyを扱える。
+// kittiesモジュールのextrinsicもこのトレイトを通すので、どちらから呼んでも振る舞いは変わらない。
+pub trait NonFungible {
+    type ItemId;
+    type AccountId;
+
+    // `id`の所有者を返す。存在しないか所有者がいなければ`None`を返す。
+    fn owner(id: Self::ItemId) -> Option<Self::AccountId>;
+    // `from`が所有する`id`を`to`へ移す。
+    fn transfer(from: Self::AccountId, to: Self::AccountId, id: Self::ItemId) -> Result;
+    // `dna`を持つ`id`を新たに生成して`to`に持たせる。
+    fn mint(to: Self::AccountId, id: Self::ItemId, dna: Self::ItemId) -> Result;
+    // `id`を削除する。
+    fn burn(id: Self::ItemId) -> Result;
+}
+
+// `system::Module::random_seed`に`subject`を混ぜてハッシュした値を返す。
+pub struct SystemRandomness<T>(PhantomData<T>);
+
+impl<T: system::Trait> Randomness<T::Hash> for SystemRandomness<T> {
+    fn random(subject: &[u8]) -> T::Hash {
+        (<system::Module<T>>::random_seed(), subject).using_encoded(<T as system::Trait>::Hashing::hash)
+    }
 }
 
 #[derive(Encode, Decode, Default, Clone, PartialEq)]
 pub struct Kitty<Hash, Balance> {
     id: Hash,       // idでkittyを唯一に識別する。
     dna: Hash,      // 個体に固有の値なのでdnaとして機能する。
-    price: Balance, // 価格
+    price: Balance, // 価格。バージョン5からは出品を`Listings`に持つので、ストレージでは常に0である。
     gen: u64,       // 性別。gender。
 }
 
+// 所有者ごとのkittyの双方向連結リストの要素。
+// `(所有者, None)`をキーとする番兵の`next`が先頭、`prev`が末尾を指す。`None`は番兵を指す。
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+pub struct KittyLinkedItem<Hash> {
+    prev: Option<Hash>, // 前のkitty
+    next: Option<Hash>, // 次のkitty
+}
+
+// モジュールのエラー。ディスパッチの結果は`&'static str`なので、`as_str`の文字列に変換して返す。
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Error {
+    KittyNotFound,         // kittyが存在しない。
+    NoOwner,               // kittyは存在するが所有者がいない。
+    NotOwner,              // kittyの所有者ではない。
+    KittyAlreadyExists,    // 同じIDのkittyがすでに存在する。
+    SelfTransfer,          // 自分自身への転送。
+    BuyOwnKitty,           // 自分のkittyを買おうとした。
+    NotForSale,            // 売りに出されていない。
+    ListingExpired,        // 出品の期限が過ぎている。
+    PriceTooHigh,          // 価格が買い手の上限を超えている。
+    BelowPriceFloor,       // 価格が最低価格を下回る。
+    Overflow,              // 桁あふれ。
+    Underflow,             // 負になる。
+    BreedingNotAllowed,    // 他人のkittyで種付け料が設定されていない。
+    InsufficientBalance,   // 手数料を払う残高がない。
+    TooManyChildren,       // 親の子供の数が上限に達している。
+    BreedingCooldown,      // 親が交配後のクールダウン中である。
+    Sterile,               // 親が交配できないようにされている。
+    GenGapTooSmall,        // 親の世代差が小さすぎる。
+    GenGapTooLarge,        // 親の世代差が大きすぎる。
+    NoTreasury,            // 手数料を受け取るトレジャリーがない。
+    BlockMintLimitReached, // このブロックで生成できるkittyの数が上限に達している。
+    NotMinter,             // kittyの生成が許されたアカウントではない。
+    CreateCooldown,        // 前回の生成からクールダウンが明けていない。
+    Gen0CapReached,        // 第0世代のkittyの数が上限に達している。
+    KittyStaked,           // ステーク中のkittyは交配できない。
+    EmptyLitter,           // 生まれる子供の数が0である。
+    LitterTooLarge,        // 生まれる子供の数が上限を超える。
+    ZeroPrice,             // 価格が0である。
+    DepositNotReserved,    // kittyの預り金が確保されていない。
+    TooManyKittiesOwned,   // 所有できるkittyの数が上限に達している。
+    GenerationFull,        // 世代のkittyの数が上限に達している。
+    NoMovableKitties,      // 動かせるkittyが1匹もない。
+    NoDepositAccount,      // 預り金を確保し直す転送先の口座がない。
+}
+
+impl Error {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Error::KittyNotFound => "Error: invalid kitty id: this kitty does not exist",
+            Error::NoOwner => "Error: this kitty exists but there is no owner for it",
+            Error::NotOwner => "Error: you have no ownership for this kitty",
+            Error::KittyAlreadyExists => "Error: the kitty coressponding to this ID already exit!",
+            Error::SelfTransfer => "Error: you can not transfer a kitty to its current owner",
+            Error::BuyOwnKitty => "Error: you can not buy your own kitty",
+            Error::NotForSale => "Error: this kitty you want to buy is not for sale",
+            Error::ListingExpired => "Error: the listing of this kitty has expired",
+            Error::PriceTooHigh => "Error: this kitty you want to buy costs more than your max price",
+            Error::BelowPriceFloor => "Error: the new price is below the price floor of this kitty",
+            Error::Overflow => "Error: Overflow happened",
+            Error::Underflow => "Error: Underflow happened",
+            Error::BreedingNotAllowed => "Error: the owner of this cat does not allow breeding with it",
+            Error::InsufficientBalance => "Error: you do not have enough balance to pay the fee",
+            Error::TooManyChildren => "Error: this cat already has too many children",
+            Error::BreedingCooldown => "Error: this cat is cooling down after breeding",
+            Error::Sterile => "Error: this cat has been made sterile and can not breed",
+            Error::GenGapTooSmall => "Error: the generations of these cats are too close to breed",
+            Error::GenGapTooLarge => "Error: the generations of these cats are too far apart to breed",
+            Error::NoTreasury => "Error: there is no treasury to receive the fee",
+            Error::BlockMintLimitReached => "Error: too many kitties have been created in this block",
+            Error::NotMinter => "Error: you are not allowed to create kitties",
+            Error::CreateCooldown => "Error: you are creating kitties too often, wait for the cooldown",
+            Error::Gen0CapReached => "Error: no more generation 0 kitties can be created",
+            Error::KittyStaked => "Error: a staked kitty can not breed",
+            Error::EmptyLitter => "Error: a litter must have at least one kitten",
+            Error::LitterTooLarge => "Error: this litter is too large",
+            Error::ZeroPrice => "Error: the price must be positive, use unlist to take a kitty off the market",
+            Error::DepositNotReserved => "Error: the deposit of this kitty is no longer reserved",
+            Error::TooManyKittiesOwned => "Error: this account can not own any more kitties",
+            Error::GenerationFull => "Error: this generation is full",
+            Error::NoMovableKitties => "Error: none of these kitties can be moved while they are locked or leased",
+            Error::NoDepositAccount => "Error: the recipient account does not exist and can not hold the deposit of this kitty",
+        }
+    }
+}
+
+impl From<Error> for &'static str {
+    fn from(error: Error) -> &'static str {
+        error.as_str()
+    }
+}
+
+// kittyがどのように生まれたか。`KittyCreated`イベントでインデクサが区別できるようにする。
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum KittyOrigin {
+    Minted,    // `create_kitty`で新しく生成された第0世代
+    Bred,      // 交配で生まれた子供
+    Genesis,   // チェーンの開始時から存在する
+    AdminMint, // rootが生成した
+    External,  // 他のモジュールが`NonFungible::mint`で生成した
+}
+
+// kittyが転送できないようにロックされている理由。ロックはモジュールの処理だけが掛け外しする。
+#[derive(Encode, Decode, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum LockReason {
+    Auction,             // オークションに出品されている。
+    DutchAuction,        // ダッチオークションに出品されている。
+    ConditionalTransfer, // 条件付き転送が提案されている。
+    Lease,               // 貸し出し中である。
+    Fractionalized,      // 持ち分に分割されている。
+    Collateral,          // 借り入れの担保になっている。
+    Staked,              // 報酬を得るためにステークされている。
+}
+
+impl LockReason {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LockReason::Auction => "Error: this kitty is locked because it is on an auction",
+            LockReason::DutchAuction => "Error: this kitty is locked because it is on a dutch auction",
+            LockReason::ConditionalTransfer => "Error: this kitty is locked because a conditional transfer is proposed for it",
+            LockReason::Lease => "Error: this kitty is locked because it is leased",
+            LockReason::Fractionalized => "Error: this kitty is locked because it is split into shares",
+            LockReason::Collateral => "Error: this kitty is locked because it is collateral for a loan",
+            LockReason::Staked => "Error: this kitty is locked because it is staked",
+        }
+    }
+}
+
+// kittyが存在するか、所有者がいるかをまとめて表す。
+// `owner_of`の`None`だけでは「一度もmintされていない」と「存在するが所有者がいない」を区別できない。
+#[derive(Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum KittyStatus<AccountId> {
+    Nonexistent,      // そのハッシュ値のkittyは存在しない。
+    Ownerless,        // kittyは存在するが所有者がいない。
+    Owned(AccountId), // kittyは存在し、`AccountId`が所有している。
+}
+
+// 1匹のkittyについてUIが必要とする情報をまとめたもの。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct KittyInfo<AccountId, Hash, Balance> {
+    kitty: Kitty<Hash, Balance>, // kitty本体
+    owner: AccountId,            // 所有者
+    global_index: u64,           // 全kittyの中で何番目か（通し番号を管理していなければ0）
+    owner_index: u64,            // 所有者にとって何番目か
+}
+
+// 1匹のkittyのプロフィール。クライアントが1回の問い合わせで表示に必要な情報を得られるようにする。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct KittyProfile<AccountId, Hash, Balance> {
+    kitty: Kitty<Hash, Balance>, // kitty本体
+    owner: Option<AccountId>,    // 所有者（所有者がいなければ`None`）
+    for_sale: bool,              // 売りに出されているか
+}
+
+// ダッチオークション。価格は`start_block`から`end_block`にかけて`start_price`から`end_price`へ線形に下がる。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct DutchAuction<AccountId, Balance, BlockNumber> {
+    seller: AccountId,        // 出品者
+    start_price: Balance,     // 開始価格
+    end_price: Balance,       // 最終価格
+    start_block: BlockNumber, // 開始ブロック
+    end_block: BlockNumber,   // 終了ブロック
+}
+
+// 競り上げ式のオークション。`end_block`になると最高入札者に落札される。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct Auction<AccountId, Balance, BlockNumber> {
+    seller: AccountId,                 // 出品者
+    min_bid: Balance,                  // 最低入札額
+    end_block: BlockNumber,            // このブロック以降は入札できず、精算される。
+    highest_bidder: Option<AccountId>, // 最高入札者
+    highest_bid: Balance,              // 最高入札額
+}
+
+// 1ブロックで自動精算するオークションの最大件数。残りは次のブロックに回す。
+const MAX_AUCTION_SETTLEMENTS_PER_BLOCK: usize = 10;
+
+// オークションごとに残す入札の件数。
+const MAX_BID_HISTORY: usize = 10;
+
+// 1ブロックで取り下げる期限切れの出品の最大数。
+const MAX_LISTING_EXPIRIES_PER_BLOCK: usize = 20;
+
+// 1ブロックで勝敗を決める受けられた対戦の最大数。残りは次のブロックに回す。
+const MAX_BATTLES_PER_BLOCK: usize = 10;
+
+// 1ブロックで処理する順番待ちの交配の最大数と、列に入れられる申し込みの最大数。
+const MAX_BREEDS_PER_BLOCK: usize = 5;
+const MAX_BREED_QUEUE_LENGTH: usize = 100;
+
+// kitty同士の交換の提案。`proposer`の持つkittyと`counterparty`の持つkittyを交換する。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct SwapProposal<AccountId, BlockNumber> {
+    proposer: AccountId,     // 提案者
+    counterparty: AccountId, // 提案時点での相手側kittyの所有者
+    expiry: BlockNumber,     // このブロックを過ぎると提案は無効になる。
+}
+
+// kittyの貸し出しの申し出。借り手が受け入れると賃料の支払いと同時に貸し出しが始まる。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct LeaseOffer<AccountId, Balance, BlockNumber> {
+    lessee: AccountId,        // 借り手
+    duration: BlockNumber,    // 貸し出し期間（ブロック数）
+    rent: Balance,            // 前払いの賃料
+}
+
+// 貸し出し中のkitty。期間中は借り手が所有者になるが、転送・出品はできない。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct Lease<AccountId, Balance, BlockNumber> {
+    lessor: AccountId,                   // 貸し手
+    lessee: AccountId,                   // 借り手
+    rent: Balance,                       // 支払われた賃料
+    expiry: BlockNumber,                 // このブロックの終わりにkittyは貸し手へ戻る。
+    end_requested_by: Option<AccountId>, // 期間前の終了に同意した側
+}
+
+// kittyを担保にした借り入れ。貸し手が資金を出すまで`lender`と`due`は`None`である。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct Loan<AccountId, Balance, BlockNumber> {
+    borrower: AccountId,       // 借り手（担保のkittyの所有者）
+    lender: Option<AccountId>, // 貸し手
+    amount: Balance,           // 元本
+    interest: Balance,         // 返済時に元本に加えて支払う利息
+    duration: BlockNumber,     // 資金を受け取ってから返済期限までのブロック数
+    due: Option<BlockNumber>,  // 返済期限。このブロックまでに返済しなければ貸し手が担保を受け取れる。
+}
+
+// 対戦の申し込み。挑戦者の賭け金は申し込み時に確保し、受けた側は同じ額を賭ける。
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct Challenge<AccountId, Hash, Balance> {
+    challenger: AccountId, // 挑戦者（挑むkittyの所有者）
+    defender: Hash,        // 挑まれたkitty
+    wager: Balance,        // 賭け金。勝った側が相手の賭け金を受け取る。
+}
+
+// 売買の代金の分け方。売り手とトレジャリー、作者の受け取りの合計はちょうど代金になる。
+struct SaleSplit<AccountId, Balance> {
+    proceeds: Balance,                     // 売り手の受け取り
+    fee: Option<(AccountId, Balance)>,     // 販売手数料を受け取るトレジャリーと手数料
+    royalty: Option<(AccountId, Balance)>, // ロイヤリティを受け取る作者とロイヤリティ
+}
+
+// kittyごとに保持する所有権移転履歴の最大件数。
+const MAX_TRANSFER_HISTORY: usize = 20;
+
+// 現在のストレージのレイアウトのバージョン。`Kitty`などのレイアウトを変えたら上げ、`_migrate_kitty`に移行処理を書く。
+const CURRENT_STORAGE_VERSION: u32 = 9;
+
+// `on_initialise`で1ブロックあたりに移行するkittyの最大数。
+const MIGRATION_BATCH_SIZE: u64 = 100;
+
+// 1匹の親が持てる子供の最大数。
+const MAX_CHILDREN_PER_KITTY: usize = 256;
+
+// `create_kitties`で一度に生成できるkittyの最大数。
+const MAX_BULK_CREATE: u32 = 20;
+
+// `set_prices`で一度にpriceを設定できるkittyの最大数。
+const MAX_BATCH_PRICES: usize = 50;
+
+// 1つの世代に存在できるkittyの最大数。`KittiesByGeneration`の一覧の長さの上限である。
+const MAX_KITTIES_PER_GENERATION: usize = 1000;
+
+// 一度の交配で生まれる子供の最大数。
+const MAX_LITTER_SIZE: u32 = 8;
+
+// ページ単位の列挙で一度に返すkittyの最大数。
+const MAX_PAGE_SIZE: u64 = 100;
+
+// 一度の統合で移せるkittyの最大数。
+const MAX_MERGE_KITTIES: u64 = 100;
+
+// 消されたアカウントから一度に`Pound`へ移すときにたどるkittyの最大数。移せなかったkittyも数える。
+const MAX_IMPOUND_BATCH: u64 = 50;
+
+// `transfer_all`で一度に転送できるkittyの最大数。
+const MAX_TRANSFER_ALL: u64 = 100;
+
+// `gift_random`が候補として調べるkittyの最大数。先頭からこの数だけが候補になる。
+const MAX_GIFT_PROBE: u64 = 100;
+
+// kittyのメタデータのURIの最大バイト数。
+const MAX_URI_LENGTH: usize = 256;
+
+// 1匹のkittyをウォッチできるアカウントの最大数。
+const MAX_WATCHERS_PER_KITTY: usize = 50;
+
+// `transfer_with_memo`のメモの最大バイト数。
+const MAX_MEMO_LENGTH: usize = 128;
+
+// 販売手数料の段階の最大数。
+const MAX_SALE_FEE_TIERS: usize = 10;
+
+// 販売手数料率の分母。手数料率は1万分率（basis point）で表す。
+const SALE_FEE_DENOMINATOR: u32 = 10_000;
+
+// 作者に払われるロイヤリティ率の上限。`SALE_FEE_DENOMINATOR`を分母とし、1000は10%。
+const MAX_ROYALTY: u32 = 1_000;
+
+// 交換の提案が有効であり続けるブロック数。
+const SWAP_PROPOSAL_PERIOD: u64 = 17280;
+
 // decl_eventマクロの適用によってブロックチェーンの状態遷移後に返されるイベントの型を定義する。
 decl_event!(
     pub enum Event<T>
         where <T as system::Trait>::AccountId,
               <T as system::Trait>::Hash,
+              <T as system::Trait>::BlockNumber,
               <T as balances::Trait>::Balance
     {
         Created(AccountId, Hash),                // `AccountId`が`Hash`で指し示されるkittyをcreateした。
+        KittyCreated(AccountId, Hash, Vec<u8>, u64, KittyOrigin, AccountId), // `AccountId`のもとに`Hash`のkittyが遺伝子`Vec<u8>`、世代`u64`で`KittyOrigin`の方法で生まれた。作者は`AccountId`。
         PriceSet(AccountId, Hash, Balance),      // `AccountId`が`Hash`で指し示されるkittyのpriceを`Balance`に設定した。
+        Delisted(AccountId, Hash),               // `AccountId`が`Hash`で指し示されるkittyの出品を取り下げた。
+        ListingExpired(Hash),                    // `Hash`で指し示されるkittyの出品が期限切れで取り下げられた。
+        UriSet(AccountId, Hash, Vec<u8>),        // `AccountId`が`Hash`のkittyのメタデータのURIを`Vec<u8>`に設定した。
+        Watched(AccountId, Hash),                // `AccountId`が`Hash`のkittyをウォッチした。
+        Unwatched(AccountId, Hash),              // `AccountId`が`Hash`のkittyのウォッチをやめた。
+        WatchedKittyListed(Hash, Balance),       // ウォッチされている`Hash`のkittyが`Balance`で売りに出された。
         Transferred(AccountId, AccountId, Hash), // `AccountId`が`AccountId`に`Hash`で指し示されるkittyをtransferした。
-        Bought(AccountId, AccountId, Hash, Balance),   // `AccountId`が`AccountId`から`Hash`で指し示されるkittyを`Balance`buyした。
+        TransferredWithMemo(AccountId, AccountId, Hash, Vec<u8>), // `AccountId`が`AccountId`に`Hash`のkittyをメモ`Vec<u8>`を付けてtransferした。
+        Bought(AccountId, AccountId, Hash, Balance, Balance), // `AccountId`が`AccountId`から`Hash`で指し示されるkittyを`Balance`buyした。売買の累計額は`Balance`になった。
+        BoughtFor(AccountId, AccountId, AccountId, Hash, Balance), // `AccountId`が`AccountId`のために`AccountId`から`Hash`のkittyを`Balance`で買った。(payer, recipient, seller, kitty, price)
+        BuyReserved(AccountId, Hash, Balance),         // `AccountId`が`Hash`のkittyの購入のために`Balance`を確保した。
+        BuyCancelled(AccountId, Hash, Balance),        // `AccountId`が`Hash`のkittyの購入を取りやめ、確保していた`Balance`を戻した。
+        Gifted(AccountId, AccountId, Hash),            // `AccountId`が`AccountId`にランダムに選ばれた`Hash`のkittyを贈った。
+        Burned(AccountId, Hash),                       // `AccountId`が`Hash`で指し示されるkittyを削除した。
+        Bred(AccountId, Hash, Hash, Hash),             // `AccountId`が`Hash`と`Hash`を親として`Hash`を誕生させた。(owner, child, parent 1, parent 2)
+        BreedRequested(AccountId, Hash, Hash),         // `AccountId`が`Hash`と`Hash`の交配を順番待ちの列に入れた。
+        BreedSkipped(AccountId, Hash, Hash),           // `AccountId`の`Hash`と`Hash`の交配は、処理するときに条件を満たさなかったので行わなかった。
+        DutchAuctionStarted(AccountId, Hash, Balance, Balance, BlockNumber), // `AccountId`が`Hash`のkittyを`Balance`から`Balance`まで`BlockNumber`ブロックで下がるダッチオークションに出品した。
+        DutchAuctionCancelled(AccountId, Hash), // `AccountId`が`Hash`のkittyのダッチオークションを取り下げた。
+        SwapProposed(AccountId, Hash, AccountId, Hash, BlockNumber), // `AccountId`が自分の`Hash`と`AccountId`の`Hash`の交換を`BlockNumber`まで有効な形で提案した。
+        SwapExecuted(AccountId, Hash, AccountId, Hash), // `AccountId`の`Hash`と`AccountId`の`Hash`が交換された。
+        SwapCancelled(AccountId, Hash, Hash),           // `AccountId`が`Hash`と`Hash`の交換の提案を取り下げた。
+        ConditionalTransferProposed(AccountId, AccountId, Hash, Balance, BlockNumber), // `AccountId`が`AccountId`に`Hash`のkittyを`Balance`の支払いと引き換えに`BlockNumber`まで有効な形で渡すことを提案した。
+        ConditionalTransferAccepted(AccountId, AccountId, Hash, Balance), // `AccountId`から`AccountId`へ`Hash`のkittyが`Balance`の支払いと引き換えに渡された。
+        ConditionalTransferCancelled(AccountId, Hash),                    // `AccountId`が`Hash`のkittyの条件付き転送の提案を取り下げた。
+        LeaseOffered(AccountId, AccountId, Hash, BlockNumber, Balance), // `AccountId`が`AccountId`に`Hash`のkittyを`BlockNumber`ブロックの間`Balance`で貸し出すことを申し出た。
+        LeaseStarted(AccountId, AccountId, Hash, BlockNumber, Balance), // `AccountId`から`AccountId`への`Hash`の貸し出しが`BlockNumber`まで`Balance`で始まった。
+        LeaseEnded(AccountId, AccountId, Hash),      // `AccountId`から`AccountId`への`Hash`の貸し出しが期限を迎え、kittyが返却された。
+        LeaseTerminated(AccountId, AccountId, Hash), // `AccountId`から`AccountId`への`Hash`の貸し出しが双方の合意で期限前に終了した。
+        LeaseReturnFailed(AccountId, Hash),          // 期限を迎えた`Hash`のkittyを貸し手`AccountId`へ返却できなかった。貸し手が取り戻すまで貸し出しは残る。
+        Fractionalized(AccountId, Hash, u64),            // `AccountId`が`Hash`のkittyを`u64`個の持ち分に分割した。
+        SharesTransferred(AccountId, AccountId, Hash, u64), // `AccountId`が`AccountId`に`Hash`のkittyの持ち分を`u64`個移した。
+        Reunified(AccountId, Hash),                      // 全持ち分を集めた`AccountId`が`Hash`のkittyを単独で所有した。
+        LoanRequested(AccountId, Hash, Balance, Balance, BlockNumber), // `AccountId`が`Hash`のkittyを担保に`Balance`を利息`Balance`、期間`BlockNumber`ブロックで借りることを申し出た。
+        LoanRequestCancelled(AccountId, Hash),          // `AccountId`が`Hash`のkittyを担保にした借り入れの申し出を取り下げた。
+        LoanFunded(AccountId, AccountId, Hash, BlockNumber), // `AccountId`が`AccountId`に`Hash`のkittyを担保として`BlockNumber`を期限に貸した。
+        LoanRepaid(AccountId, AccountId, Hash, Balance),     // `AccountId`が`AccountId`に`Balance`を返済し、`Hash`のkittyの担保が外れた。
+        CollateralLiquidated(AccountId, AccountId, Hash, Balance, Balance), // 返済されなかったので`AccountId`が`AccountId`から担保の`Hash`のkittyを受け取った。(lender, borrower, kitty, principal, interest)
+        LoanGracePeriodSet(BlockNumber),                     // 返済期限後に担保を受け取れるまでの猶予を`BlockNumber`ブロックに設定した。
+        ChallengeIssued(AccountId, Hash, Hash, Balance), // `AccountId`が`Hash`のkittyで`Hash`のkittyに賭け金`Balance`の対戦を申し込んだ。
+        ChallengeCancelled(AccountId, Hash),            // `AccountId`が`Hash`のkittyでの対戦の申し込みを取り下げた。
+        ChallengeAccepted(AccountId, Hash, BlockNumber), // `AccountId`が`Hash`のkittyからの対戦を受けた。勝敗は`BlockNumber`のブロックの終わりに決まる。
+        BattleResolved(Hash, Hash, u32, u32, Option<AccountId>, Balance), // `Hash`のkittyと`Hash`のkittyが`u32`対`u32`で対戦し、`AccountId`が賭け金`Balance`を得た。引き分けなら`None`で、双方に賭け金を返した。
+        KeepListingsOnTransferSet(AccountId, bool), // `AccountId`が転送時に出品を残すかを`bool`に設定した。
+        PriceFloorSet(AccountId, Hash, Balance), // `AccountId`が`Hash`のkittyの最低価格を`Balance`に設定した。
+        StudFeeSet(AccountId, Hash, Balance), // `AccountId`が`Hash`のkittyの種付け料を`Balance`に設定した。
+        Sterilized(Hash),                     // `Hash`のkittyが二度と交配できなくなった。
+        AuctionStarted(AccountId, Hash, Balance, BlockNumber), // `AccountId`が`Hash`のkittyを最低入札額`Balance`、終了ブロック`BlockNumber`でオークションに出品した。
+        BidPlaced(AccountId, Hash, Balance),             // `AccountId`が`Hash`のkittyに`Balance`で入札した。
+        AuctionSettled(AccountId, AccountId, Hash, Balance), // `AccountId`の`Hash`のkittyを`AccountId`が`Balance`で落札した。
+        AuctionCancelled(AccountId, Hash),               // `AccountId`の`Hash`のkittyのオークションが落札されずに終了した。
+        AuctionFailed(AccountId, Hash, Balance, Balance), // `AccountId`の`Hash`のkittyのオークションは最高入札額`Balance`が最低落札価格`Balance`に届かず、売れなかった。
+        MergeProposed(AccountId, AccountId),          // `AccountId`が自分のkittyをすべて`AccountId`へ統合することを提案した。
+        CollectionsMerged(AccountId, AccountId, u64, u64), // `AccountId`の`u64`匹のkittyが`AccountId`へ統合された。残りは`u64`匹。
+        TransferredAll(AccountId, AccountId, u64, u64), // `AccountId`が`AccountId`へ`u64`匹のkittyをまとめて転送した。残りは`u64`匹。
+        BreedingCooldownSet(BlockNumber, BlockNumber, u32), // 交配後のクールダウンを基本`BlockNumber`ブロック、ウィンドウ`BlockNumber`ブロック、閾値`u32`回に設定した。
+        GenGapSet(u64, Option<u64>), // 交配できる親の世代差を`u64`以上`Option<u64>`以下に設定した。
+        BreedFeeSet(Balance, Option<AccountId>, bool), // 交配手数料を`Balance`、受け取り先のトレジャリーを`Option<AccountId>`、焼却するかを`bool`に設定した。
+        SaleFeeTiersSet(Vec<(Balance, u32)>), // 販売手数料の段階を(累計販売額`Balance`, 手数料率`u32`/10000)の表に設定した。
+        CreatorRoyaltySet(AccountId, u32),    // `AccountId`がこれから生成するkittyのロイヤリティ率を`u32`/10000に設定した。
+        RoyaltyPaid(AccountId, Hash, Balance), // `Hash`のkittyが売れたので作者の`AccountId`に`Balance`のロイヤリティが払われた。
+        SaleFeePaid(AccountId, Hash, Balance), // `Hash`のkittyが売れたのでトレジャリーの`AccountId`に`Balance`の販売手数料が払われた。
+        StorageMigrated(u32), // ストレージのレイアウトがバージョン`u32`に移行された。
+        RateLimitSet(u32, BlockNumber), // 1アカウントあたり`BlockNumber`ブロックごとに`u32`回までに呼び出しを制限した。
+        MaxAuctionsPerAccountSet(u32),  // 1アカウントが同時に開催できるオークションの数を`u32`までに制限した。
+        BidHistoryRetentionSet(BlockNumber), // 精算したオークションの入札の記録を`BlockNumber`ブロックの間残すようにした。
+        AuctionExtensionSet(BlockNumber, u32), // 終了`BlockNumber`ブロック前からの入札でオークションを延長し、延長は`u32`回までにした。
+        AuctionExtended(Hash, BlockNumber),  // 終了間際の入札で`Hash`のkittyのオークションが`BlockNumber`ブロックまで延長された。
+        MintAllowlistEnabled(bool),     // `create_kitty`を許可リストのアカウントに限るかを`bool`に設定した。
+        MarketToggled(bool),            // 売買を有効にするかを`bool`に設定した。
+        MaxMintsPerBlockSet(u32),       // 1ブロックで`create_kitty`により生成できるkittyの数を`u32`までに制限した。
+        CreateCooldownSet(BlockNumber), // 1アカウントが`create_kitty`を呼んでから次に呼べるまでを`BlockNumber`ブロックに設定した。
+        MinterAdded(AccountId),         // `AccountId`が`create_kitty`を呼べるようになった。
+        MinterRemoved(AccountId),       // `AccountId`が`create_kitty`を呼べなくなった。
+        KittyDepositSet(Balance),       // `create_kitty`で1匹ごとに確保する預り金を`Balance`に設定した。
+        StakingRewardSet(Option<AccountId>, Balance), // ステークの報酬を`Option<AccountId>`から1ブロックあたり`Balance`払うように設定した。
+        KittyStaked(AccountId, Hash),               // `AccountId`が`Hash`のkittyをステークした。
+        KittyUnstaked(AccountId, Hash, BlockNumber), // `AccountId`が`BlockNumber`ブロックの間ステークしていた`Hash`のkittyを引き出した。
+        StakingRewardPaid(AccountId, Hash, Balance), // `Hash`のkittyのステークの報酬として`AccountId`に`Balance`が払われた。
+        StakingRewardUnpaid(AccountId, Hash, Balance), // 報酬の原資が足りず、`Hash`のkittyの`Balance`の報酬を`AccountId`に払えなかった。
+        CreationFeeSet(Balance),        // `create_kitty`で1匹ごとに払う手数料を`Balance`に設定した。
+        MaxKittiesOwnedSet(u64),        // 1アカウントが所有できるkittyの数を`u64`匹までに制限した。
+        PoundSet(Option<AccountId>),    // 消されたアカウントのkittyを引き取るアカウントを`Option<AccountId>`に設定した。
+        KittiesImpounded(AccountId, AccountId, u64, u64), // 消された`AccountId`のkittyを`AccountId`が`u64`匹引き取った。残りは`u64`匹。
+        Surrendered(AccountId, Hash),   // `AccountId`が`Hash`のkittyを`Pound`に手放した。
+        Adopted(AccountId, Hash, Balance), // `AccountId`が`Pound`の`Hash`のkittyを手数料`Balance`で引き取った。
+        AdoptionFeeSet(Balance),        // `Pound`のkittyを引き取るときの手数料を`Balance`に設定した。
     }
 );
 
@@ -70,11 +532,229 @@ decl_storage! {
         AllKittiesIndex: map T::Hash => u64;                           // hash value => kitty's index
 
         // OwnedKitty get(kitty_of_owner): map T::AccountId => T::Hash;   // account ID => hash value
-        OwnedKittiesArray get(kitty_of_owner_by_index): map (T::AccountId, u64) => T::Hash; // (account ID, the index of owned kitty) => hash value
+        // 所有者ごとのkittyの双方向連結リスト。転送ではポインタを付け替えるだけで、他のkittyの順番は変わらない。
+        OwnedKittiesList get(owned_kitties_list): map (T::AccountId, Option<T::Hash>) => Option<KittyLinkedItem<T::Hash>>; // (account ID, hash value or None for the sentinel) => linked item
         OwnedKittiesCount get(owned_kitty_count): map T::AccountId => u64; // account ID => count of owned kitties
+
+        // ストレージのバージョン2までの所有者ごとのリスト。移行で`OwnedKittiesList`へ移して削除する。
+        OwnedKitties: double_map T::AccountId, blake2_256(u64) => T::Hash; // account ID, the index of owned kitty => hash value
+        OwnedKittiesArray: map (T::AccountId, u64) => T::Hash; // (account ID, the index of owned kitty) => hash value
         OwnedKittiesIndex: map T::Hash => u64; // そのkittyが所有者にとって何番目のkittyなのかを返す。
 
         Nonce: u64;
+
+        // `create_kitty`を呼べるアカウント。`RestrictMinting`がtrueのときだけ使い、falseなら誰でも生成できる。交配は誰でもできる。
+        RestrictMinting get(restrict_minting): bool;
+        Minters get(is_minter): map T::AccountId => bool; // account ID => allowed to create kitties
+
+        // 1ブロックで`create_kitty`により生成できるkittyの数と、直近に生成があったブロックでの生成数。
+        // `MaxMintsPerBlock`が0のときは制限しない。交配で生まれるkittyは数えない。
+        MaxMintsPerBlock get(max_mints_per_block): u32;
+        MintsInBlock get(mints_in_block): (T::BlockNumber, u32); // (block number, kitties created in the block)
+
+        // これまでに`create_kitty`で生成した第0世代のkittyの数。削除しても減らない。上限は`Trait::GEN0_CAP`。
+        // 導入前に生成されたkittyは数えていない。
+        Gen0Count get(gen0_count): u64;
+
+        // アカウントごとの`create_kitty`のクールダウン（ブロック数）と、最後に生成したブロック。`CreateCooldown`が0のときは制限しない。
+        CreateCooldown get(create_cooldown) config(): T::BlockNumber;
+        LastCreateBlock get(last_create_block): map T::AccountId => Option<T::BlockNumber>; // account ID => block number of the last creation
+
+        // `create_kitty`で1匹ごとに`Treasury`へ払う手数料。0なら取らない。チェーンの開始時にも設定できる。
+        CreationFee get(creation_fee) config(): T::Balance;
+
+        // 1アカウントが所有できるkittyの最大数。0なら制限しない。チェーンの開始時にも設定できる。
+        MaxKittiesOwned get(max_kitties_owned) config(): u64;
+
+        // `create_kitty`で1匹ごとに確保する預り金と、kittyごとに確保されている額。0のときは預り金を取らない。
+        // 預り金はkittyとともに所有者の間を移り、`burn_kitty`でそのときの所有者に戻る。
+        KittyDeposit get(kitty_deposit): T::Balance;
+        KittyDeposits get(deposit_of): map T::Hash => T::Balance; // hash value => deposit reserved from the current owner
+
+        // 残高が0になって消されたアカウントのkittyを引き取るアカウント。
+        // 一度に引き取れなかったkittyが残るアカウントは`Reaped`に記録し、誰でも`rescue_kitties`で続きを引き取れる。
+        Pound get(pound): Option<T::AccountId>;
+        Reaped get(is_reaped): map T::AccountId => bool; // account ID => reaped with kitties left
+
+        // `Pound`にいて、誰でも`adopt_kitty`で引き取れるkittyと、引き取るときに`Treasury`へ払う手数料。
+        Adoptable get(is_adoptable): map T::Hash => bool; // hash value => waiting for adoption in the pound
+
+        // ステーク中のkittyと、アカウントごとのステーク中のkittyの数。
+        // 報酬は`RewardPot`から、ステークしていたブロック数 * `RewardPerBlock`を世代 + 1で割った額を払う。
+        StakedKitties get(staked_kitty): map T::Hash => Option<(T::AccountId, T::BlockNumber)>; // hash value => (staker, block number staked at)
+        StakedCount get(staked_count): map T::AccountId => u64; // account ID => count of staked kitties
+        RewardPot get(reward_pot): Option<T::AccountId>;
+        RewardPerBlock get(reward_per_block): T::Balance;
+        AdoptionFee get(adoption_fee): T::Balance;
+
+        // trueなら、そのアカウントから転送したkittyの出品と最低価格を取り消さない。
+        KeepListingsOnTransfer get(keep_listings_on_transfer): map T::AccountId => bool; // account ID => keep listings on transfer
+
+        // 出品の期限。このブロックを過ぎると売りに出されていないものとして扱う。記録がなければ期限はない。
+        ListingExpiry get(listing_expiry): map T::Hash => Option<T::BlockNumber>; // hash value => last block the listing is valid
+        ListingsExpiringAt get(listings_expiring_at): map T::BlockNumber => Vec<T::Hash>; // block number => kitties whose listing expires after the block
+
+        // 世代ごとの現存するkittyの数と、これまでに生まれた最も新しい世代。
+        // 移行中は、どのkittyをすでに数えたかを`GenCounted`に記録して二重に数えないようにする。
+        KittiesPerGen get(kitties_in_gen): map u64 => u64; // generation => number of living kitties
+        HighestGen get(highest_gen): u64;
+        GenCounted: map T::Hash => bool; // hash value => counted in `KittiesPerGen` during the migration
+
+        // 世代ごとの現存するkittyの一覧。1つの世代には`MAX_KITTIES_PER_GENERATION`匹までしか生まれない。
+        KittiesByGeneration get(kitties_in_generation): map u64 => Vec<T::Hash>; // generation => hash values
+
+        // 売りに出されているkittyの価格。出品していないkittyは記録がない。
+        // 出品のたびに`Kitties`を書き直さないよう、`Kitty`の`price`から分けている。
+        Listings get(listing): map T::Hash => Option<T::Balance>; // hash value => sale price
+
+        // 売りに出されている（priceが0でない）kittyの数
+        ForSaleCount get(for_sale_count): u64;
+
+        // 売りに出されているkittyの一覧。全kittyのリストと同じくSwap and Popメソッドで詰める。
+        // 一覧の長さは移行が終われば`ForSaleCount`と一致するが、移行中はまだ載せていない出品の分だけ短い。
+        ForSaleArray get(for_sale_by_index): map u64 => T::Hash; // the index of listed kitty => hash value
+        ForSaleArrayCount get(for_sale_array_count): u64;
+        ForSaleIndex: map T::Hash => u64; // hash value => the index of listed kitty
+
+        // これまでで最も高額な売買
+        HighestSale get(highest_sale): Option<(T::Hash, T::AccountId, T::AccountId, T::Balance, T::BlockNumber)>; // (kitty, buyer, seller, price, block number)
+
+        // 売り手の確認を待っている購入。買い手は代金を確保している。
+        PendingBuys get(pending_buy): map (T::Hash, T::AccountId) => Option<T::Balance>; // (hash value, buyer) => reserved price
+
+        // ストレージのレイアウトのバージョンと、移行中のkittyの位置（全kittyのインデックス）
+        StorageVersion get(storage_version): u32;
+        MigrationCursor get(migration_cursor): u64;
+
+        // 出品価格の下限。所有者がうっかり安く売らないようにする。
+        PriceFloor get(price_floor): map T::Hash => T::Balance; // hash value => minimum price
+
+        // オフチェーンの画像やメタデータを指すURI（IPFSのCIDやURL）。ERC721の`tokenURI`にあたり、転送しても残る。
+        KittyUri get(uri_of): map T::Hash => Vec<u8>; // hash value => metadata URI
+        Watchers get(watchers_of): map T::Hash => Vec<T::AccountId>; // hash value => accounts notified when the kitty is listed
+
+        // 他人が親として使うときに支払う種付け料。0なら他人は親として使えない。
+        StudFee get(stud_fee): map T::Hash => T::Balance; // hash value => stud fee
+        Sterile get(is_sterile): map T::Hash => bool; // hash value => never breeds again
+
+        // kittyの遺伝子。`Kitty`の`dna`はハッシュと同じ幅なので、遺伝は`GENOME_LENGTH`バイトのこちらで扱う。
+        // 遺伝子が導入される前のkittyは記録がなく、`genome_of`が`dna`から導く。
+        Genomes: map T::Hash => Vec<u8>; // hash value => genome
+
+        // 親子関係。交配で生まれたkittyだけが親を持つ。
+        Parents get(parents_of): map T::Hash => Option<(T::Hash, T::Hash)>; // hash value => (parent 1, parent 2)
+        Children get(children_of): map T::Hash => Vec<T::Hash>;             // hash value => children (at most `MAX_CHILDREN_PER_KITTY`)
+
+        // 交配できる親の世代差の範囲。`MaxGenGap`が`None`なら上限はない。
+        MinGenGap get(min_gen_gap): u64;
+        MaxGenGap get(max_gen_gap): Option<u64>;
+
+        // 交配手数料。`BurnBreedFees`がtrueなら焼却し、falseなら`Treasury`へ送る。
+        BreedFee get(breed_fee): T::Balance;
+        Treasury get(treasury): Option<T::AccountId>;
+        BurnBreedFees get(burn_breed_fees): bool;
+
+        // 売買（出品・購入・オークション）が有効か。無効にしても交配と転送はできる。
+        MarketEnabled get(market_enabled): bool = true;
+
+        // `buy_kitty`の販売手数料。売り手の累計販売額が多いほど手数料率が下がり、手数料は`Treasury`へ送る。
+        SaleFeeTiers get(sale_fee_tiers): Vec<(T::Balance, u32)>; // [(minimum lifetime sell volume, fee in basis points)] in ascending order of volume
+        SellerVolume get(seller_volume): map T::AccountId => T::Balance; // account ID => lifetime sell volume
+
+        // kittyを生み出したアカウント。mint時に記録し、売買や転送では変わらない。
+        // バージョン9より前のkittyは移行時の所有者を作者とする。
+        KittyCreator get(creator_of): map T::Hash => Option<T::AccountId>; // hash value => creator
+
+        // 作者が生成したkittyに付けるロイヤリティ率と、生成時に記録したkittyごとのロイヤリティ率。率が0のkittyは記録しない。
+        CreatorRoyalty get(creator_royalty): map T::AccountId => u32; // account ID => royalty in basis points for kitties created from now on
+        KittyRoyalty get(royalty_rate_of): map T::Hash => u32; // hash value => royalty in basis points
+
+        // これまでのすべての売買の累計額
+        TotalVolume get(total_volume): T::Balance;
+
+        // `request_breed`で申し込まれ、`on_finalise`で順に処理される交配
+        BreedQueue get(breed_queue): Vec<(T::AccountId, T::Hash, T::Hash)>; // [(breeder, parent 1, parent 2)] in request order
+
+        // 交配後のクールダウン。直近の交配数が多いほど長くなる。
+        BreedingCooldownUntil get(breeding_cooldown_until): map T::Hash => T::BlockNumber; // hash value => block number until which the kitty can not breed
+        BaseBreedingCooldown get(base_breeding_cooldown) config(): T::BlockNumber;  // 基本のクールダウン（ブロック数）
+        CongestionWindow get(congestion_window): T::BlockNumber;          // 混雑度を測るウィンドウの長さ（ブロック数）
+        CongestionWindowStart get(congestion_window_start): T::BlockNumber; // 現在のウィンドウの開始ブロック
+        CongestionThreshold get(congestion_threshold): u32;               // クールダウンを`BaseBreedingCooldown`だけ延ばす交配数
+        RecentBreedings get(recent_breedings): u32;                       // 直近の交配数（ウィンドウごとに半減する）
+
+        // コレクションの統合の提案
+        MergeProposals get(merge_proposal): map T::AccountId => Option<T::AccountId>; // from account ID => into account ID
+
+        // アカウントごとの呼び出し回数の制限。`MaxCallsPerWindow`が0のときは制限しない。
+        MaxCallsPerWindow get(max_calls_per_window): u32;                    // 1ウィンドウあたりの最大呼び出し回数
+        RateWindow get(rate_window): T::BlockNumber;                          // ウィンドウの長さ（ブロック数）
+        RateLimit get(rate_limit): map T::AccountId => (u32, T::BlockNumber); // account ID => (calls in the window, window start)
+
+        // これまでに現れたDNAとその種類の数。kittyが削除されてもこの記録は消さない。
+        SeenDna: map T::Hash => bool; // dna => has been seen
+        DnaPatternCount get(dna_pattern_count): u64;
+
+        // 一度でもmintされたことのあるkittyのハッシュ値。kittyが削除されてもこの記録は消さない。
+        EverExisted: map T::Hash => bool; // hash value => has ever been minted
+
+        // kittyの来歴。新しい所有者とその時のブロック番号を古い順に最大`MAX_TRANSFER_HISTORY`件記録する。
+        TransferHistory get(transfer_history): map T::Hash => Vec<(T::AccountId, T::BlockNumber)>; // hash value => [(owner, block number)]
+
+        // 開催中のダッチオークション
+        DutchAuctions get(dutch_auction): map T::Hash => Option<DutchAuction<T::AccountId, T::Balance, T::BlockNumber>>; // hash value => auction
+
+        // 支払いと引き換えにkittyを渡す条件付き転送の提案
+        PendingSwaps get(pending_swap): map T::Hash => Option<(T::AccountId, T::AccountId, T::Balance, T::BlockNumber)>; // hash value => (proposer, recipient, required payment, deadline)
+
+        // 貸し出しの申し出と貸し出し中のkitty
+        LeaseOffers get(lease_offer): map T::Hash => Option<LeaseOffer<T::AccountId, T::Balance, T::BlockNumber>>; // hash value => offer
+        Leases get(lease): map T::Hash => Option<Lease<T::AccountId, T::Balance, T::BlockNumber>>; // hash value => lease
+        LeasesExpiringAt: map T::BlockNumber => Vec<T::Hash>; // block number => kitties whose lease expires at the end of the block
+
+        // 開催中のオークションと、終了ブロックごとのオークションの一覧
+        Auctions get(auction): map T::Hash => Option<Auction<T::AccountId, T::Balance, T::BlockNumber>>; // hash value => auction
+        AuctionsExpiringAt get(auctions_expiring_at): map T::BlockNumber => Vec<T::Hash>; // block number => kitties whose auction ends at the block
+        // 最低落札価格のあるオークション。最高入札額がこれに届かなければ売れない。`bool`がtrueなら届かない入札を受け付けない。
+        AuctionReserves get(auction_reserve): map T::Hash => Option<(T::Balance, bool)>; // hash value => (reserve price, reject bids below reserve)
+        // 即決価格のあるオークション。入札額が即決価格に届くまでは誰でもこの価格で即座に買える。
+        AuctionBuyNow get(auction_buy_now): map T::Hash => Option<T::Balance>; // hash value => buy-now price
+
+        // オークションの直近`MAX_BID_HISTORY`件の入札（古い順）。精算後も`BidHistoryRetention`ブロックの間は読める。
+        BidHistory get(bid_history): map T::Hash => Vec<(T::AccountId, T::Balance, T::BlockNumber)>; // hash value => [(bidder, amount, block number)]
+        BidHistoryRetention get(bid_history_retention): T::BlockNumber;
+        BidHistoriesExpiringAt: map T::BlockNumber => Vec<T::Hash>; // block number => kitties whose bid history is removed at the block
+
+        // 終了間際の入札でオークションを延長する設定と、オークションごとの延長回数。`AuctionExtensionWindow`が0なら延長しない。
+        AuctionExtensionWindow get(auction_extension_window): T::BlockNumber;
+        MaxAuctionExtensions get(max_auction_extensions): u32;
+        AuctionExtensions get(auction_extensions): map T::Hash => u32; // hash value => number of times the auction has been extended
+
+        // アカウントごとの開催中のオークションの数と、その上限。`MaxAuctionsPerAccount`が0のときは制限しない。
+        MaxAuctionsPerAccount get(max_auctions_per_account): u32;
+        ActiveAuctions get(active_auctions): map T::AccountId => u32; // account ID => number of running auctions
+
+        // 転送できないようにロックされているkittyとその理由
+        KittyLocks get(kitty_lock): map T::Hash => Option<LockReason>; // hash value => lock reason
+
+        // 持ち分に分割されたkittyの持ち分の総数と、アカウントごとの持ち分
+        TotalShares get(total_shares): map T::Hash => u64; // hash value => total shares
+        KittyShares get(kitty_shares): map (T::Hash, T::AccountId) => u64; // (hash value, account ID) => shares
+
+        // kittyを担保にした借り入れ
+        Loans get(loan): map T::Hash => Option<Loan<T::AccountId, T::Balance, T::BlockNumber>>; // hash value => loan
+        // 返済期限の後、貸し手が担保を受け取れるようになるまでの猶予（ブロック数）。猶予の間は借り手がまだ返済できる。
+        LoanGracePeriod get(loan_grace_period): T::BlockNumber;
+
+        // 挑戦者のkittyごとの対戦の申し込み
+        Challenges get(pending_challenge): map T::Hash => Option<Challenge<T::AccountId, T::Hash, T::Balance>>; // hash value of challenger kitty => challenge
+        ChallengeExpiry get(challenge_expiry): map T::Hash => Option<T::BlockNumber>; // hash value of challenger kitty => last block the challenge can be accepted
+        // 受けられた対戦と、賭け金を確保した受けた側のアカウント。勝敗は`BattlesResolvingAt`のブロックの`on_finalise`で決まる。
+        AcceptedChallenges get(accepted_challenge): map T::Hash => Option<T::AccountId>; // hash value of challenger kitty => defender's owner
+        BattlesResolvingAt get(battles_resolving_at): map T::BlockNumber => Vec<T::Hash>; // block number => challenger kitties whose battle is resolved after the block
+
+        // kitty同士の交換の提案
+        SwapProposals get(swap_proposal): map (T::Hash, T::Hash) => Option<SwapProposal<T::AccountId, T::BlockNumber>>; // (proposer's kitty, counterparty's kitty) => proposal
     }
 }
 // decl_moduleマクロの適用によってチェーンに刻むデータへのアクセスインタフェースの実装を記述する。
@@ -91,277 +771,5233 @@ decl_module! {
 
             // Verify first, write lastの原則：create_kitty()を叩いたsenderの正当性を確認する。
             let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
 
-            // nonceを計算する。
-            let nonce = <Nonce<T>>::get();
+            Self::_create_kitties(sender, 1)
+        }
 
-            // creat_kitty()を叩いたsenderからnonceと合わせてハッシュ値を計算する。
-            // 「random_hash <--> kitty」は一対一対応している。
-            let random_hash = (<system::Module<T>>::random_seed(), &sender, nonce)
-                .using_encoded(<T as system::Trait>::Hashing::hash);
+        // 配布のために`count`匹のkittyを一度に生成する関数。どれか1匹でも生成できなければ1匹も生成しない。
+        fn create_kitties(origin, count: u32) -> Result {
 
-            // 計算したrandom_hashが衝突していないことを確認する。
-            ensure!(!<KittyOwner<T>>::exists(random_hash), "the kitty coressponding to this ID already exit!");
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            ensure!(count > 0, "Error: you must create at least one kitty");
+            ensure!(count <= MAX_BULK_CREATE, "Error: you can not create this many kitties at once");
 
-            // new_kittyを生成する。
-            let new_kitty = Kitty {
-                id: random_hash,
-                dna: random_hash,
-                price: <T::Balance as As<u64>>::sa(0),
-                gen: 0,
+            Self::_create_kitties(sender, count)
+        }
+
+        // kittyのIDと新しいpriceを与えて、kittyのpriceを更新する関数を定義する。
+        // `expires_in`を与えると、そのブロック数が過ぎた出品は買えなくなる。
+        fn set_price(origin, kitty_id: T::Hash, new_price: T::Balance, expires_in: Option<T::BlockNumber>) -> Result {
+
+            // Verify first, write lastの原則：create_kitty()を叩いたsenderの正当性を確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：senderがこのkittyにこのpriceを設定できることを確認する。
+            Self::_ensure_can_set_price(&sender, kitty_id, new_price)?;
+
+            // Verify first, write lastの原則：期限は未来のブロックでなければならない。
+            let expiry = match expires_in {
+                Some(expires_in) => {
+                    ensure!(!expires_in.is_zero(), "Error: the listing must stay valid for at least one block");
+                    let now = <system::Module<T>>::block_number();
+                    Some(now.checked_add(&expires_in).ok_or(Error::Overflow)?)
+                },
+                None => None,
             };
 
-            // 新たに生成されたkittyを記録する。
-            Self::_mint(sender, random_hash, new_kitty)?;
+            // kittyをkitty IDで引き出して、priceを更新して、書き戻す。`_set_price`は前の期限を取り消す。
+            Self::_set_price(kitty_id, new_price);
+            if let Some(expiry) = expiry {
+                <ListingExpiry<T>>::insert(kitty_id, expiry);
+                <ListingsExpiringAt<T>>::mutate(expiry, |kitties| kitties.push(kitty_id));
+            }
 
-            // Nonceをインクリメント
-            <Nonce<T>>::mutate(|n| {
-                *n += 1
-            });
+            // ブロックチェーンの状態が遷移したので、それを通知するイベントを吐く。
+            Self::deposit_event(RawEvent::PriceSet(sender, kitty_id, new_price));
 
             Ok(())
         }
 
-        // kittyのIDと新しいpriceを与えて、kittyのpriceを更新する関数を定義する。
-        fn set_price(origin, kitty_id: T::Hash, new_price: T::Balance) -> Result {
+        // kittyのメタデータのURIを設定する関数。空のURIを与えると消す。
+        fn set_uri(origin, kitty_id: T::Hash, uri: Vec<u8>) -> Result {
 
-            // Verify first, write lastの原則：create_kitty()を叩いたsenderの正当性を確認する。
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
             let sender = ensure_signed(origin)?;
 
-            // Verify first, write lastの原則：指定したkittyが存在することを確認する。
-            ensure!(<Kitties<T>>::exists(kitty_id), "Error: invalid kitty id: this kitty does not exist");
+            // Verify first, write lastの原則：呼び出し回数を数える前に、URIが長すぎないことを確認する。
+            ensure!(uri.len() <= MAX_URI_LENGTH, "Error: the URI is too long");
+            Self::_check_rate_limit(&sender)?;
 
             // Verify first, write lastの原則：本当にそのkittyはあなたのもの？
-            let owner = Self::owner_of(kitty_id).ok_or("Error: there is no owner for this kitty")?; // そもそも所有者のいないkittyだった。
-            ensure!(owner == sender, "Error: you have no ownership to this kitty"); // あなたのkittyではなかった。
+            Self::ensure_owner(&sender, kitty_id)?;
 
-            // kittyをkitty IDで引き出して、priceを更新して、書き戻す。
-            let mut kitty = Self::kitty(kitty_id);
-            kitty.price = new_price;
-            <Kitties<T>>::insert(kitty_id, kitty);
+            if uri.is_empty() {
+                <KittyUri<T>>::remove(kitty_id);
+            } else {
+                <KittyUri<T>>::insert(kitty_id, &uri);
+            }
 
-            // ブロックチェーンの状態が遷移したので、それを通知するイベントを吐く。
-            Self::deposit_event(RawEvent::PriceSet(sender, kitty_id, new_price));
+            Self::deposit_event(RawEvent::UriSet(sender, kitty_id, uri));
 
             Ok(())
         }
 
-        // 呼び出し側が転送先を指定してkittyを転送し、その成否を返す関数を定義する。
-        fn transfer(origin, to: T::AccountId, kitty_id: T::Hash) -> Result {
+        // これから生成する第0世代のkittyのロイヤリティ率を1万分率で設定する関数。すでに生成したkittyの率は変わらない。
+        fn set_creator_royalty(origin, royalty: u32) -> Result {
 
             // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
             let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            ensure!(royalty <= MAX_ROYALTY, "Error: the royalty can not exceed 10%");
 
-            // Verify first, write lastの原則：転送したいkittyの存在を確認する。
-            let owner = Self::owner_of(kitty_id).ok_or("Error: there is no owner for this kitty")?;
-            ensure!(owner == sender, "Error: you have no ownership for this kitty");
+            <CreatorRoyalty<T>>::insert(&sender, royalty);
 
-            // 転送をする。
-            Self::_transfer_from(sender, to, kitty_id)?;
+            Self::deposit_event(RawEvent::CreatorRoyaltySet(sender, royalty));
 
             Ok(())
         }
 
-        // 呼び出し側が買いたいkittyのIDと買取額を引数に与えて、購入を実行し、その成否を返す関数を定義する。
-        fn buy_kitty(origin, kitty_id: T::Hash, max_price: T::Balance) -> Result {
+        // kittyをウォッチする関数。ウォッチしているkittyが売りに出されると`WatchedKittyListed`が通知される。
+        fn watch(origin, kitty_id: T::Hash) -> Result {
 
             // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
             let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
 
-            // Verify first, write lastの原則：買いたいkittyが存在することを確認する。
-            ensure!(<Kitties<T>>::exists(kitty_id), "Error: invalid kitty id: this kitty does not exist");
-
-            // Verify first, write lastの原則：kittyの所有者が正当であることを確認する。
-            let owner = Self::owner_of(kitty_id).ok_or("Error: there is no owner for this kitty")?;
-            ensure!(owner != sender, "Error: you can not buy your own kitty");
+            // Verify first, write lastの原則：kittyが存在し、まだウォッチしておらず、ウォッチする人が多すぎないことを確認する。
+            ensure!(<Kitties<T>>::exists(kitty_id), Error::KittyNotFound.as_str());
+            let mut watchers = Self::watchers_of(kitty_id);
+            ensure!(!watchers.contains(&sender), "Error: you are already watching this kitty");
+            ensure!(watchers.len() < MAX_WATCHERS_PER_KITTY, "Error: this kitty has too many watchers");
 
-            // 売買されるkittyを引き出す。
-            let mut kitty = Self::kitty(kitty_id);
-            // 売却額を確認する。
-            let kitty_price = kitty.price;
+            watchers.push(sender.clone());
+            <Watchers<T>>::insert(kitty_id, watchers);
 
-            // 売却額 == 0のkittyは売却対象ではないものとする。
-            ensure!(!kitty_price.is_zero(), "Error: this kitty you want to buy is not for sale");
+            Self::deposit_event(RawEvent::Watched(sender, kitty_id));
 
-            // 買取側の口座残高が売却額以下でないと買えないので確認する。
-            ensure!(kitty_price <= max_price, "Error: this kitty you want to buy costs more than your max price");
+            Ok(())
+        }
 
-            // 双方の残高をアトミックに更新する。
-            <balances::Module<T>>::make_transfer(&sender, &owner, kitty_price)?;
+        // kittyのウォッチをやめる関数。
+        fn unwatch(origin, kitty_id: T::Hash) -> Result {
 
-            // kittyを売却側から購入側へ転送する。
-            Self::_transfer_from(owner.clone(), sender.clone(), kitty_id)?;
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
 
-            kitty.price = <T::Balance as As<u64>>::sa(0);
+            // Verify first, write lastの原則：本当にそのkittyをウォッチしている？
+            let mut watchers = Self::watchers_of(kitty_id);
+            let position = watchers.iter().position(|w| *w == sender)
+                .ok_or("Error: you are not watching this kitty")?;
 
-            <Kitties<T>>::insert(kitty_id, kitty);
+            watchers.swap_remove(position);
+            if watchers.is_empty() {
+                <Watchers<T>>::remove(kitty_id);
+            } else {
+                <Watchers<T>>::insert(kitty_id, watchers);
+            }
 
-            // Boughtイベントを吐く。
-            Self::deposit_event(RawEvent::Bought(sender, owner, kitty_id, kitty_price));
+            Self::deposit_event(RawEvent::Unwatched(sender, kitty_id));
 
             Ok(())
         }
 
-        // 親となる二匹を引数として与えて、子供を作らせ、その成否を返す関数。
-        fn breed_kitty(origin, kitty_id_1: T::Hash, kitty_id_2: T::Hash) -> Result {
+        // 売りに出しているkittyの出品を取り下げる関数。最低価格はそのまま残す。
+        fn unlist(origin, kitty_id: T::Hash) -> Result {
 
             // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
             let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
 
-            // Verify first, write lastの原則：kittyの存在確認。
-            ensure!(<Kitties<T>>::exists(kitty_id_1), "Error: this cat 1 does not exist");
-            ensure!(<Kitties<T>>::exists(kitty_id_2), "Error: this cat 2 does not exist");
+            // Verify first, write lastの原則：本当にそのkittyはあなたのもの？売りに出している？
+            Self::ensure_owner(&sender, kitty_id)?;
+            ensure!(!Self::price_of(kitty_id).is_zero(), Error::NotForSale.as_str());
 
-            // 子供に振られるidを計算する。
-            let nonce = <Nonce<T>>::get();
-            let random_hash = (<system::Module<T>>::random_seed(), &sender, nonce)
-                                .using_encoded(<T as system::Trait>::Hashing::hash);
+            Self::_set_price(kitty_id, Zero::zero());
 
-            // 親を引き出す。
-            let kitty_1 = Self::kitty(kitty_id_1);
-            let kitty_2 = Self::kitty(kitty_id_2);
+            Self::deposit_event(RawEvent::Delisted(sender, kitty_id));
 
-            // 最終的な子供のDNA（初期値として片親のDNAをコピー）
-            let mut final_dna = kitty_1.dna;
+            Ok(())
+        }
 
-            // DNAをシェイキング！
-            for (i, (dna_2_element, r)) in kitty_2.dna.as_ref().iter().zip(random_hash.as_ref().iter()).enumerate() {
-                if r % 2 == 0 {
-                    final_dna.as_mut()[i] = *dna_2_element;
-                }
-            }
+        // 複数のkittyのpriceをまとめて更新する関数。1匹でも設定できなければ1匹も更新しない。
+        fn set_prices(origin, items: Vec<(T::Hash, T::Balance)>) -> Result {
 
-            // 子供誕生
-            let new_kitty = Kitty {
-                id: random_hash,
-                dna: final_dna,
-                price: <T::Balance as As<u64>>::sa(0),
-                gen: cmp::max(kitty_1.gen, kitty_2.gen) + 1,
-            };
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            ensure!(!items.is_empty(), "Error: you must set at least one price");
+            ensure!(items.len() <= MAX_BATCH_PRICES, "Error: you can not set this many prices at once");
 
-            // 子供の所有権を記録する。
-            Self::_mint(sender, random_hash, new_kitty)?;
+            // Verify first, write lastの原則：書き込む前に、すべてのkittyについてpriceを設定できることを確認する。
+            for (index, (kitty_id, new_price)) in items.iter().enumerate() {
+                ensure!(!items[..index].iter().any(|(id, _)| id == kitty_id), "Error: the same kitty appears more than once");
+                Self::_ensure_can_set_price(&sender, *kitty_id, *new_price)?;
+            }
 
-            // nonce更新
-            <Nonce<T>>::mutate(|n| *n += 1);
+            for (kitty_id, new_price) in items {
+                Self::_set_price(kitty_id, new_price);
+                Self::deposit_event(RawEvent::PriceSet(sender.clone(), kitty_id, new_price));
+            }
 
             Ok(())
-
         }
-    }
-}
 
-impl <T: Trait> Module<T> {
+        // 呼び出し側が転送先を指定してkittyを転送し、その成否を返す関数を定義する。
+        fn transfer(origin, to: T::AccountId, kitty_id: T::Hash) -> Result {
 
-    // 新たなkittyを記録するヘルパー関数を用意。
-    fn _mint(to: T::AccountId, kitty_id: T::Hash, new_kitty: Kitty<T::Hash, T::Balance>) -> Result {
-        // 計算したrandom_hashが衝突していないことを確認する。
-        ensure!(!<KittyOwner<T>>::exists(kitty_id), "Error: the kitty coressponding to this ID already exit!");
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
 
-        // Verify first, write lastの原則：この人が現在何匹のkittyを所有しているかを取得する。
-        let owned_kitty_count = Self::owned_kitty_count(&to);
+            // Verify first, write lastの原則：転送したいkittyの存在を確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
 
-        // Verify first, write lastの原則：新しいkittyを所有するので更新する。
-        let new_owned_kitty_count = owned_kitty_count.checked_add(1)
-            .ok_or("Error: Overflow happed when trying to register a new kitty in your account balance")?;
+            // Verify first, write lastの原則：kittyを動かす前に、手数料を払えることを確認する。
+            let fee = Self::_ensure_can_pay_transfer_fee(&sender, 1)?;
 
-        // Verify first, write lastの原則：現在登録されているkittiesの個体数を確認する。
-        let all_kitties_count = Self::all_kitties_count();
+            // 転送をして、手数料を払う。
+            <Self as NonFungible>::transfer(sender.clone(), to, kitty_id)?;
+            Self::_pay_transfer_fee(&sender, fee)?;
 
-        // Verify first, write lastの原則：これから登録しようとしているkittyを追加してoverflowしないかを確認する。
-        let new_all_kitties_count = all_kitties_count.checked_add(1)
-            .ok_or("Error: Overflow happened when trying to register a new kitty")?;
+            Ok(())
+        }
 
-        // (random_hash, new_kitty)を登録する。
-        <Kitties<T>>::insert(kitty_id, new_kitty);
+        // 自分のkittyの一覧での位置（0始まり）でkittyを指定して転送する関数。ハッシュ値を覚えていないCLIなどのため。
+        // 一覧は連結リストなので、転送や削除で他のkittyの位置が入れ替わることはなく、後ろのkittyが1つずつ前に詰まる。
+        fn transfer_by_index(origin, to: T::AccountId, index: u64) -> Result {
 
-        // (生成者を一意に区別するハッシュ値, 生成者)を登録する。
-        <KittyOwner<T>>::insert(kitty_id, &to);
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
 
-        // (all_kitties_count, random_hash)を登録する。all_kitties_countは0オリジンの通し番号となる。
-        <AllKittiesArray<T>>::insert(all_kitties_count, kitty_id);
+            // Verify first, write lastの原則：位置が所有しているkittyの数より小さいことを確認する。
+            ensure!(index < Self::owned_kitty_count(&sender), "Error: there is no kitty at this index");
+            let kitty_id = Self::_owned_kitty_ids(&sender, index, 1).pop()
+                .ok_or("Error: there is no kitty at this index")?;
+            Self::_ensure_not_leased(kitty_id)?;
 
-        // 「現在のkittiesの個体数」を更新する。
-        <AllKittiesCount<T>>::put(new_all_kitties_count);
+            // Verify first, write lastの原則：kittyを動かす前に、手数料を払えることを確認する。
+            let fee = Self::_ensure_can_pay_transfer_fee(&sender, 1)?;
 
-        // (random_hash, all_kitties_count)を登録する。
-        <AllKittiesIndex<T>>::insert(kitty_id, all_kitties_count);
+            // 転送をして、手数料を払う。
+            <Self as NonFungible>::transfer(sender.clone(), to, kitty_id)?;
+            Self::_pay_transfer_fee(&sender, fee)?;
 
-        // // (生成者, 生成者を一意に区別するハッシュ値)を登録する。
-        // <OwnedKitty<T>>::insert(&sender, random_hash);
+            Ok(())
+        }
 
-        // ((ユーザー, その人にとって何匹目か), kittyの識別子)を記録する。
-        // こうすることで、二次元リストをエミュレートする。「誰の」「何番目か」で一匹を指定できる。
-        <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count), kitty_id);
+        // 入金IDや請求書番号などのメモを付けてkittyを転送する関数。メモはイベントにだけ載せ、ストレージには残さない。
+        fn transfer_with_memo(origin, to: T::AccountId, kitty_id: T::Hash, memo: Vec<u8>) -> Result {
 
-        // (ユーザー, ユーザーの所有しているkittyの個体数)を登録する。
-        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count);
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
 
-        // 今生成されたkittyが、その所有者にとって何番目のkittyなのかを登録する。
-        <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count);
+            // Verify first, write lastの原則：呼び出し回数を数える前に、メモが長すぎないことを確認する。
+            ensure!(memo.len() <= MAX_MEMO_LENGTH, "Error: the memo is too long");
+            Self::_check_rate_limit(&sender)?;
 
-        // トランザクション執行後のイベントを吐く。
-        Self::deposit_event(RawEvent::Created(to, kitty_id));
+            // Verify first, write lastの原則：転送したいkittyの所有者であることを確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
 
-        Ok(())
-    }
+            // Verify first, write lastの原則：kittyを動かす前に、手数料を払えることを確認する。
+            let fee = Self::_ensure_can_pay_transfer_fee(&sender, 1)?;
 
-    // 転送元と転送先、転送されるkittyを特定するハッシュ値を引数に、転送を実行しその成否を返すヘルパー関数
-    fn _transfer_from(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash) -> Result {
+            // 転送をして、手数料を払う。
+            <Self as NonFungible>::transfer(sender.clone(), to.clone(), kitty_id)?;
+            Self::_pay_transfer_fee(&sender, fee)?;
 
-        // Verify first, write lastの原則：呼び出し元が転送したいkittyの所有者であるかを確認する。
-        let owner = Self::owner_of(kitty_id).ok_or("Error: there is no owner for this kitty")?;
-        ensure!(owner == from, "Error: `from` account have no ownership for this kitty");
+            Self::deposit_event(RawEvent::TransferredWithMemo(sender, to, kitty_id, memo));
 
-        // 所有者の中の何番目のkittyを転送したいのかを確認する。
-        let owned_kitty_count_from = Self::owned_kitty_count(&from);
+            Ok(())
+        }
 
-        // 転送先では何番目のkittyとして扱われるのかを確認する。
-        let owned_kitty_count_to = Self::owned_kitty_count(&to);
+        // 所有するkittyを先頭から最大`MAX_TRANSFER_ALL`匹見て、動かせるものをまとめて`to`へ転送する関数。
+        // ロック中や貸し出し中のkittyは飛ばして残りに数える。残りがあればイベントで知らせるので、
+        // 呼び出し側はロックが外れるのを待って繰り返す。1匹も動かせないときだけ失敗する。
+        fn transfer_all(origin, to: T::AccountId) -> Result {
 
-        // 転送先がすでにn匹のkittyを所有しているならば、転送先ではn+1匹目として扱われることを確認する。
-        let new_owned_kitty_count_to = owned_kitty_count_to.checked_add(1)
-            .ok_or("Error: happend overflow of `to`'s kitty balance while executing transfer method")?;
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            ensure!(sender != to, Error::SelfTransfer.as_str());
 
-        // 転送元がn匹のkittyを所有しているならば、転送してしまうと所有している個体数が1減ることを確認する。
-        let new_owned_kitty_count_from = owned_kitty_count_from.checked_sub(1)
-            .ok_or("Error: happend underflow of `from`'s kitty balance while executing transfer method")?;
+            // Verify first, write lastの原則：動かせるkittyを選び、それをすべて移せることを書き込む前に確認する。
+            // 連結リストから外しても他のkittyの順番は変わらないので、先に取り出したハッシュ値の列をそのまま使える。
+            let owned = Self::owned_kitty_count(&sender);
+            let kitty_ids = Self::_movable_kitty_ids(&sender, MAX_TRANSFER_ALL);
+            ensure!(owned == 0 || !kitty_ids.is_empty(), Error::NoMovableKitties.as_str());
+            let count = kitty_ids.len() as u64;
+            let new_owned_kitty_count_to = Self::owned_kitty_count(&to).checked_add(count).ok_or(Error::Overflow)?;
+            Self::_ensure_can_own(new_owned_kitty_count_to)?;
 
-        // 転送されるkittyが転送前の所有者にとって何番目の個体なのかを確認する。
-        let kitty_index = <OwnedKittiesIndex<T>>::get(kitty_id);
+            // Verify first, write lastの原則：kittyを動かす前に、移す匹数分の手数料を払えることを確認する。
+            let fee = Self::_ensure_can_pay_transfer_fee(&sender, count)?;
 
-        // Swap and Popメソッドで転送を実施する。
-        // 転送したいkittyが転送前所有者の最直近に得た個体だったらPopすればいい。
-        // そうでないならば、転送されるkittyの位置に、最直近で得た個体へのポインタを貼る（Swap）。
-        if kitty_index != new_owned_kitty_count_from {
+            for kitty_id in kitty_ids {
+                Self::_transfer_from(sender.clone(), to.clone(), kitty_id)?;
+            }
+            Self::_pay_transfer_fee(&sender, fee)?;
 
-            // 転送元が最直近に所有権を得たkittyのインデックスを確認する。
-            let last_kitty_id = <OwnedKittiesArray<T>>::get((from.clone(), new_owned_kitty_count_from));
+            Self::deposit_event(RawEvent::TransferredAll(sender, to, count, owned - count));
 
-            <OwnedKittiesArray<T>>::insert((from.clone(), kitty_index), last_kitty_id);
-            <OwnedKittiesIndex<T>>::insert(last_kitty_id, kitty_index);
+            Ok(())
+        }
+
+        // 呼び出し側が買いたいkittyのIDと買取額を引数に与えて、購入を実行し、その成否を返す関数を定義する。
+        fn buy_kitty(origin, kitty_id: T::Hash, max_price: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // 購入してkittyを自分に転送する。
+            let (owner, kitty_price) = Self::_buy_kitty(&sender, sender.clone(), kitty_id, max_price)?;
+
+            // Boughtイベントを吐く。
+            Self::deposit_event(RawEvent::Bought(sender, owner, kitty_id, kitty_price, Self::total_volume()));
+
+            Ok(())
+        }
+
+        // `buy_kitty`と同じ条件でkittyを購入し、自分ではなく`recipient`へ届ける関数。代金は呼び出した人が払う。
+        fn buy_kitty_for(origin, recipient: T::AccountId, kitty_id: T::Hash, max_price: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // 購入してkittyを`recipient`に転送する。
+            let (owner, kitty_price) = Self::_buy_kitty(&sender, recipient.clone(), kitty_id, max_price)?;
+
+            Self::deposit_event(RawEvent::BoughtFor(sender, recipient, owner, kitty_id, kitty_price));
+
+            Ok(())
+        }
+
+        // `buy_kitty`と同じ条件で購入を申し込み、代金をすぐには払わずに確保しておく関数。
+        // 売り手が`settle_buy`で確認すると代金が移ってkittyが転送される。それまでは`cancel_buy`で取りやめられる。
+        fn reserve_buy(origin, kitty_id: T::Hash, max_price: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // Verify first, write lastの原則：買いたいkittyが存在し、所有者が正当であることを確認する。
+            let owner = Self::_existing_owner_of(kitty_id)?;
+            ensure!(owner != sender, Error::BuyOwnKitty.as_str());
+            Self::_ensure_not_leased(kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+            ensure!(!<PendingBuys<T>>::exists((kitty_id, sender.clone())), "Error: you have already reserved funds to buy this kitty");
+
+            // Verify first, write lastの原則：売りに出されていて、価格が上限以下であることを確認する。
+            let kitty_price = Self::price_of(kitty_id);
+            ensure!(!kitty_price.is_zero(), Error::NotForSale.as_str());
+            ensure!(!Self::_listing_expired(kitty_id), Error::ListingExpired.as_str());
+            ensure!(kitty_price <= max_price, Error::PriceTooHigh.as_str());
+
+            // 代金を確保する。確保した額は精算か取りやめまで引き出せない。
+            <balances::Module<T>>::reserve(&sender, kitty_price)?;
+            <PendingBuys<T>>::insert((kitty_id, sender.clone()), kitty_price);
+
+            Self::deposit_event(RawEvent::BuyReserved(sender, kitty_id, kitty_price));
+
+            Ok(())
+        }
+
+        // 売り手が`buyer`の購入を確認し、確保されていた代金を受け取ってkittyを渡す関数。
+        fn settle_buy(origin, kitty_id: T::Hash, buyer: T::AccountId) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // Verify first, write lastの原則：購入の申し込みがあり、自分がまだ所有者で、転送できることを確認する。
+            let price = Self::pending_buy((kitty_id, buyer.clone())).ok_or("Error: there is no pending buy for this kitty from this buyer")?;
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+
+            // Verify first, write lastの原則：代金を動かした後にkittyの転送が失敗しないよう、先に転送できることを確認する。
+            Self::_ensure_can_transfer(&sender, &buyer, kitty_id)?;
+
+            // Verify first, write lastの原則：確保されていた代金から、販売手数料とロイヤリティを払えることを確認する。
+            let split = Self::_sale_split(kitty_id, &sender, price)?;
+            Self::_ensure_can_pay_sale_from_reserved(&buyer, &sender, price, &split)?;
+
+            // 確保されていた代金を分けて移し、kittyを買い手へ転送する。
+            Self::_pay_sale_from_reserved(&buyer, &sender, kitty_id, split)?;
+            <PendingBuys<T>>::remove((kitty_id, buyer.clone()));
+            Self::_transfer_from(sender.clone(), buyer.clone(), kitty_id)?;
+
+            // 売れたので出品を取り下げる。
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+
+            Self::_record_sale(kitty_id, &buyer, &sender, price);
+            Self::deposit_event(RawEvent::Bought(buyer, sender, kitty_id, price, Self::total_volume()));
+
+            Ok(())
+        }
+
+        // 買い手が購入の申し込みを取りやめ、確保していた代金を戻す関数。
+        fn cancel_buy(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let price = Self::pending_buy((kitty_id, sender.clone())).ok_or("Error: you have no pending buy for this kitty")?;
+
+            <balances::Module<T>>::unreserve(&sender, price);
+            <PendingBuys<T>>::remove((kitty_id, sender.clone()));
+
+            Self::deposit_event(RawEvent::BuyCancelled(sender, kitty_id, price));
+
+            Ok(())
+        }
+
+        // 親となる二匹を引数として与えて、子供を作らせ、その成否を返す関数。
+        // `litter_size`を指定すると一度に複数の子供が生まれる（`None`なら1匹）。手数料とクールダウンは一度の交配につき1回である。
+        fn breed_kitty(origin, kitty_id_1: T::Hash, kitty_id_2: T::Hash, litter_size: Option<u32>) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            Self::_breed(sender, kitty_id_1, kitty_id_2, litter_size.unwrap_or(1))
+        }
+
+        // 交配をすぐには行わず、順番待ちの列に入れる関数。1ブロックで行う交配の数を抑えて負荷をならす。
+        // 列に入れるときに確認した条件は処理するときにもう一度確認し、満たさなくなった申し込みは飛ばす。
+        fn request_breed(origin, kitty_id_1: T::Hash, kitty_id_2: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：列に空きがあることを確認する。
+            let mut queue = Self::breed_queue();
+            ensure!(queue.len() < MAX_BREED_QUEUE_LENGTH, "Error: the breeding queue is full");
+
+            // Verify first, write lastの原則：親が存在し、自分のものか種付け料が設定されていて、クールダウン中でないことを確認する。
+            let now = <system::Module<T>>::block_number();
+            for parent_id in [kitty_id_1, kitty_id_2].iter() {
+                let parent_owner = Self::_existing_owner_of(*parent_id)?;
+                ensure!(parent_owner == sender || !Self::stud_fee(*parent_id).is_zero(), Error::BreedingNotAllowed.as_str());
+                ensure!(!Self::is_sterile(*parent_id), Error::Sterile.as_str());
+                ensure!(!<StakedKitties<T>>::exists(*parent_id), Error::KittyStaked.as_str());
+                ensure!(now >= Self::breeding_cooldown_until(*parent_id), Error::BreedingCooldown.as_str());
+            }
+
+            queue.push((sender.clone(), kitty_id_1, kitty_id_2));
+            <BreedQueue<T>>::put(queue);
+
+            Self::deposit_event(RawEvent::BreedRequested(sender, kitty_id_1, kitty_id_2));
+
+            Ok(())
+        }
+
+        // rootが交配できる親の世代差の範囲を設定する関数。
+        fn set_gen_gap(origin, min_gap: u64, max_gap: Option<u64>) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(max_gap.map_or(true, |max_gap| min_gap <= max_gap), "Error: the minimum generation gap must not exceed the maximum");
+
+            <MinGenGap<T>>::put(min_gap);
+            match max_gap {
+                Some(max_gap) => <MaxGenGap<T>>::put(max_gap),
+                None => <MaxGenGap<T>>::kill(),
+            }
+
+            Self::deposit_event(RawEvent::GenGapSet(min_gap, max_gap));
+
+            Ok(())
+        }
+
+        // rootが交配手数料とその行き先を設定する関数。
+        fn set_breed_fee(origin, fee: T::Balance, treasury: Option<T::AccountId>, burn: bool) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(fee.is_zero() || burn || treasury.is_some(), "Error: a breeding fee must be either burned or sent to a treasury");
+
+            <BreedFee<T>>::put(fee);
+            match treasury {
+                Some(ref account) => <Treasury<T>>::put(account),
+                None => <Treasury<T>>::kill(),
+            }
+            <BurnBreedFees<T>>::put(burn);
+
+            Self::deposit_event(RawEvent::BreedFeeSet(fee, treasury, burn));
+
+            Ok(())
+        }
+
+        // rootが販売手数料の段階を設定する関数。`tiers`は(累計販売額の下限, 手数料率/10000)を累計販売額の昇順に並べる。
+        // 空にすると手数料はかからない。
+        fn set_sale_fee_tiers(origin, tiers: Vec<(T::Balance, u32)>) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(tiers.len() <= MAX_SALE_FEE_TIERS, "Error: too many sale fee tiers");
+            ensure!(tiers.iter().all(|(_, rate)| *rate < SALE_FEE_DENOMINATOR), "Error: a sale fee rate must be below 100%");
+            ensure!(tiers.windows(2).all(|pair| pair[0].0 < pair[1].0), "Error: sale fee tiers must be in ascending order of volume");
+            ensure!(tiers.iter().all(|(_, rate)| *rate == 0) || Self::treasury().is_some(), Error::NoTreasury.as_str());
+
+            <SaleFeeTiers<T>>::put(&tiers);
+
+            Self::deposit_event(RawEvent::SaleFeeTiersSet(tiers));
+
+            Ok(())
+        }
+
+        // rootが交配後のクールダウンを設定する関数。
+        // クールダウンは`base`ブロックを基本に、直近`window`ブロックの交配数が`threshold`回増えるごとに`base`ブロックずつ延びる。
+        fn set_breeding_cooldown(origin, base: T::BlockNumber, window: T::BlockNumber, threshold: u32) -> Result {
+
+            ensure_root(origin)?;
+
+            <BaseBreedingCooldown<T>>::put(base);
+            <CongestionWindow<T>>::put(window);
+            <CongestionThreshold<T>>::put(threshold);
+
+            Self::deposit_event(RawEvent::BreedingCooldownSet(base, window, threshold));
+
+            Ok(())
+        }
+
+        // rootがストレージの移行を最大`limit`匹分進める関数。`on_initialise`を待たずに移行を終わらせたいときに使う。
+        fn migrate_storage(origin, limit: u64) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(Self::storage_version() < CURRENT_STORAGE_VERSION, "Error: the storage is already up to date");
+
+            Self::_migrate_storage(limit);
+
+            Ok(())
+        }
+
+        // ストレージのバージョンが古ければ、1ブロックあたり`MIGRATION_BATCH_SIZE`匹ずつ移行する。
+        // 混雑度のウィンドウが切り替わるたびに直近の交配数を半減させる。
+        fn on_initialise(n: T::BlockNumber) {
+            if Self::storage_version() < CURRENT_STORAGE_VERSION {
+                Self::_migrate_storage(MIGRATION_BATCH_SIZE);
+            }
+
+            let window = Self::congestion_window();
+            if !window.is_zero() && n >= Self::congestion_window_start().saturating_add(window) {
+                <RecentBreedings<T>>::mutate(|count| *count /= 2);
+                <CongestionWindowStart<T>>::put(n);
+            }
+        }
+
+        // 自分のkittyの中からランダムに1匹を選んで`to`に贈る関数。
+        // 連結リストの先頭から最大`MAX_GIFT_PROBE`匹だけを候補とし、乱数で選んだ位置から順に調べて最初に贈れるkittyを選ぶ。
+        fn gift_random(origin, to: T::AccountId) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：贈れるkittyを持っていることを確認する。
+            // たどるのは先頭から最大`MAX_GIFT_PROBE`匹だけなので、所有数によらず読む量は一定。
+            let candidates = Self::_owned_kitty_ids(&sender, 0, MAX_GIFT_PROBE);
+            ensure!(!candidates.is_empty(), "Error: you have no kitty to gift");
+
+            // 乱数で調べ始める位置を選ぶ。
+            let nonce = <Nonce<T>>::get();
+            let random_hash = (T::Randomness::random(b"gift_random"), &sender, nonce)
+                .using_encoded(<T as system::Trait>::Hashing::hash);
+            let random_value = u64::decode(&mut random_hash.as_ref()).unwrap_or_default();
+            let start = (random_value % candidates.len() as u64) as usize;
+
+            // Verify first, write lastの原則：貸し出し中やロックされたkittyは贈れないので、選んだ位置から順に飛ばす。
+            let kitty_id = (0..candidates.len())
+                .map(|offset| candidates[(start + offset) % candidates.len()])
+                .find(|kitty_id| !<Leases<T>>::exists(*kitty_id) && Self::kitty_lock(*kitty_id).is_none())
+                .ok_or("Error: all of your kitties are leased or locked")?;
+            let new_nonce = nonce.checked_add(1).ok_or(Error::Overflow)?;
+
+            // 転送をする。
+            Self::_transfer_from(sender.clone(), to.clone(), kitty_id)?;
+
+            // nonce更新
+            <Nonce<T>>::put(new_nonce);
+
+            Self::deposit_event(RawEvent::Gifted(sender, to, kitty_id));
+
+            Ok(())
+        }
+
+        // 自分のkittyを削除する関数。一度存在したことは`EverExisted`に残る。
+        fn burn_kitty(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：本当にそのkittyはあなたのもの？
+            Self::ensure_owner(&sender, kitty_id)?;
+
+            <Self as NonFungible>::burn(kitty_id)
+        }
+
+        // 自分が転送したkittyの出品と最低価格を残すかを設定する関数。
+        fn set_keep_listings_on_transfer(origin, keep: bool) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            if keep {
+                <KeepListingsOnTransfer<T>>::insert(&sender, true);
+            } else {
+                <KeepListingsOnTransfer<T>>::remove(&sender);
+            }
+
+            Self::deposit_event(RawEvent::KeepListingsOnTransferSet(sender, keep));
+
+            Ok(())
+        }
+
+        // 自分のkittyの出品価格の下限を設定する関数。
+        fn set_price_floor(origin, kitty_id: T::Hash, floor: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：本当にそのkittyはあなたのもの？
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+
+            <PriceFloor<T>>::insert(kitty_id, floor);
+
+            Self::deposit_event(RawEvent::PriceFloorSet(sender, kitty_id, floor));
+
+            Ok(())
+        }
+
+        // 自分のkittyの種付け料を設定する関数。0にすると他人は親として使えなくなる。
+        fn set_stud_fee(origin, kitty_id: T::Hash, fee: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：本当にそのkittyはあなたのもの？
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+
+            <StudFee<T>>::insert(kitty_id, fee);
+
+            Self::deposit_event(RawEvent::StudFeeSet(sender, kitty_id, fee));
+
+            Ok(())
+        }
+
+        // 希少性を保つため、自分のkittyを二度と交配できないようにする関数。取り消すことはできない。
+        fn make_sterile(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：本当にそのkittyはあなたのもの？
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            ensure!(!Self::is_sterile(kitty_id), "Error: this kitty is already sterile");
+
+            <Sterile<T>>::insert(kitty_id, true);
+
+            Self::deposit_event(RawEvent::Sterilized(kitty_id));
+
+            Ok(())
+        }
+
+        // 自分のkittyをダッチオークションに出品する関数。
+        fn start_dutch(origin, kitty_id: T::Hash, start_price: T::Balance, end_price: T::Balance, duration: T::BlockNumber) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // Verify first, write lastの原則：出品したいkittyの所有者であることを確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+
+            // Verify first, write lastの原則：オークションのパラメータが正しいことを確認する。
+            ensure!(!<DutchAuctions<T>>::exists(kitty_id), "Error: this kitty is already on a dutch auction");
+            ensure!(!<Auctions<T>>::exists(kitty_id), "Error: this kitty is on an auction");
+            ensure!(!duration.is_zero(), "Error: the duration of a dutch auction must be positive");
+            ensure!(!end_price.is_zero(), "Error: the end price of a dutch auction must be positive");
+            ensure!(end_price <= start_price, "Error: the price of a dutch auction can not increase");
+            Self::_ensure_unlocked(kitty_id)?;
+
+            let start_block = <system::Module<T>>::block_number();
+            let end_block = start_block.checked_add(&duration).ok_or(Error::Overflow)?;
+            let auction = DutchAuction {
+                seller: sender.clone(),
+                start_price,
+                end_price,
+                start_block,
+                end_block,
+            };
+            <DutchAuctions<T>>::insert(kitty_id, auction);
+            Self::lock_kitty(kitty_id, LockReason::DutchAuction)?;
+
+            Self::deposit_event(RawEvent::DutchAuctionStarted(sender, kitty_id, start_price, end_price, duration));
+
+            Ok(())
+        }
+
+        // 出品者がダッチオークションを取り下げる関数。
+        fn cancel_dutch(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let auction = Self::dutch_auction(kitty_id).ok_or("Error: this kitty is not on a dutch auction")?;
+            ensure!(auction.seller == sender, "Error: you are not the seller of this dutch auction");
+
+            <DutchAuctions<T>>::remove(kitty_id);
+            Self::unlock_kitty(kitty_id);
+
+            Self::deposit_event(RawEvent::DutchAuctionCancelled(sender, kitty_id));
+
+            Ok(())
+        }
+
+        // ダッチオークションに出品されたkittyを現在価格で購入する関数。
+        // `min_acceptable_price`を指定すると、現在価格がそれを下回るとき（想定より遅れて取り込まれたとき）に購入を拒否する。
+        fn buy_dutch(origin, kitty_id: T::Hash, max_price: T::Balance, min_acceptable_price: Option<T::Balance>) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // Verify first, write lastの原則：オークションが開催中で、出品者がまだ所有者であることを確認する。
+            let auction = Self::dutch_auction(kitty_id).ok_or("Error: this kitty is not on a dutch auction")?;
+            let owner = Self::_existing_owner_of(kitty_id)?;
+            ensure!(owner == auction.seller, "Error: the seller of this dutch auction no longer owns the kitty");
+            Self::_ensure_not_leased(kitty_id)?;
+            ensure!(owner != sender, Error::BuyOwnKitty.as_str());
+
+            // 現在価格が許容範囲内にあることを確認する。
+            let price = Self::dutch_price(&auction, <system::Module<T>>::block_number());
+            ensure!(price <= max_price, Error::PriceTooHigh.as_str());
+            if let Some(min_price) = min_acceptable_price {
+                ensure!(price >= min_price, "Error: the current dutch auction price is below your minimum acceptable price");
+            }
+
+            // Verify first, write lastの原則：代金を動かした後にkittyの転送が失敗しないよう、ロックを外せば転送できることを確認する。
+            Self::_ensure_can_transfer_locked(&owner, &sender, kitty_id, LockReason::DutchAuction)?;
+
+            // Verify first, write lastの原則：販売手数料とロイヤリティを含め、代金を分けて払えることを確認する。
+            let split = Self::_sale_split(kitty_id, &owner, price)?;
+            Self::_ensure_can_pay_sale(&sender, <balances::Module<T>>::free_balance(&sender), &owner, price, &split)?;
+
+            // 代金を出品者とトレジャリー、作者へ払う。
+            Self::_pay_sale(&sender, &owner, kitty_id, split)?;
+
+            // オークションのロックを外し、kittyを売却側から購入側へ転送する。
+            Self::unlock_kitty(kitty_id);
+            Self::_transfer_from(owner.clone(), sender.clone(), kitty_id)?;
+
+            <DutchAuctions<T>>::remove(kitty_id);
+
+            // Boughtイベントを吐く。
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+            Self::_record_sale(kitty_id, &sender, &owner, price);
+            Self::deposit_event(RawEvent::Bought(sender, owner, kitty_id, price, Self::total_volume()));
+
+            Ok(())
+        }
+
+        // 自分のkittyを`duration`ブロックの間オークションに出品する関数。
+        fn start_auction(origin, kitty_id: T::Hash, min_bid: T::Balance, duration: T::BlockNumber, reserve_price: T::Balance, reject_below_reserve: bool, buy_now_price: Option<T::Balance>) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // Verify first, write lastの原則：出品したいkittyの所有者であることを確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+
+            // Verify first, write lastの原則：オークションのパラメータが正しいことを確認する。
+            ensure!(!<Auctions<T>>::exists(kitty_id), "Error: this kitty is already on an auction");
+            ensure!(!<DutchAuctions<T>>::exists(kitty_id), "Error: this kitty is on a dutch auction");
+            ensure!(!duration.is_zero(), "Error: the duration of an auction must be positive");
+            if let Some(buy_now_price) = buy_now_price {
+                ensure!(buy_now_price >= min_bid, "Error: the buy-now price must not be below the minimum bid");
+                ensure!(buy_now_price >= reserve_price, "Error: the buy-now price must not be below the reserve price");
+            }
+            Self::_ensure_unlocked(kitty_id)?;
+
+            // Verify first, write lastの原則：同時に開催できるオークションの数を超えないことを確認する。
+            let active_auctions = Self::active_auctions(&sender);
+            let max_auctions = Self::max_auctions_per_account();
+            ensure!(max_auctions == 0 || active_auctions < max_auctions, "Error: you are already running the maximum number of auctions");
+            let new_active_auctions = active_auctions.checked_add(1)
+                .ok_or(Error::Overflow)?;
+
+            let end_block = <system::Module<T>>::block_number().checked_add(&duration).ok_or(Error::Overflow)?;
+            <Auctions<T>>::insert(kitty_id, Auction {
+                seller: sender.clone(),
+                min_bid,
+                end_block,
+                highest_bidder: None,
+                highest_bid: <T::Balance as As<u64>>::sa(0),
+            });
+            <AuctionsExpiringAt<T>>::mutate(end_block, |kitties| kitties.push(kitty_id));
+            // 最低落札価格が0ならどの入札でも売れる。
+            if reserve_price.is_zero() {
+                <AuctionReserves<T>>::remove(kitty_id);
+            } else {
+                <AuctionReserves<T>>::insert(kitty_id, (reserve_price, reject_below_reserve));
+            }
+            match buy_now_price {
+                Some(buy_now_price) => <AuctionBuyNow<T>>::insert(kitty_id, buy_now_price),
+                None => <AuctionBuyNow<T>>::remove(kitty_id),
+            }
+            <ActiveAuctions<T>>::insert(&sender, new_active_auctions);
+            // 前のオークションの入札と延長回数が残っていれば消しておく。
+            <BidHistory<T>>::remove(kitty_id);
+            <AuctionExtensions<T>>::remove(kitty_id);
+            Self::lock_kitty(kitty_id, LockReason::Auction)?;
+
+            Self::deposit_event(RawEvent::AuctionStarted(sender, kitty_id, min_bid, end_block));
+
+            Ok(())
+        }
+
+        // オークションに入札する関数。最低入札額以上かつ現在の最高入札額より高くなければならない。
+        fn bid(origin, kitty_id: T::Hash, amount: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // Verify first, write lastの原則：オークションが開催中であることを確認する。
+            let mut auction = Self::auction(kitty_id).ok_or("Error: this kitty is not on an auction")?;
+            ensure!(<system::Module<T>>::block_number() < auction.end_block, "Error: this auction has already ended");
+            ensure!(auction.seller != sender, "Error: you can not bid on your own kitty");
+
+            // Verify first, write lastの原則：入札額が十分で、支払えることを確認する。
+            ensure!(amount >= auction.min_bid, "Error: your bid is below the minimum bid");
+            ensure!(auction.highest_bidder.is_none() || amount > auction.highest_bid, "Error: your bid is not higher than the current highest bid");
+            if let Some((reserve_price, true)) = Self::auction_reserve(kitty_id) {
+                ensure!(amount >= reserve_price, "Error: your bid is below the reserve price");
+            }
+
+            // Verify first, write lastの原則：自分の前の入札を返しても、新しい入札額を確保できることを確認する。
+            let raising_own_bid = auction.highest_bidder.as_ref() == Some(&sender);
+            let available = if raising_own_bid {
+                <balances::Module<T>>::free_balance(&sender).saturating_add(auction.highest_bid)
+            } else {
+                <balances::Module<T>>::free_balance(&sender)
+            };
+            ensure!(available >= amount, "Error: you do not have enough free balance for this bid");
+
+            // 終了まで`AuctionExtensionWindow`ブロックを切った入札なら、他の入札者が応じられるよう終了ブロックを延ばす。
+            // 延長は`MaxAuctionExtensions`回までで、オークションがいつまでも終わらないことはない。
+            let now = <system::Module<T>>::block_number();
+            let window = Self::auction_extension_window();
+            let extensions = Self::auction_extensions(kitty_id);
+            let new_end = now.checked_add(&window).ok_or(Error::Overflow)?;
+            let extend = !window.is_zero()
+                && new_end > auction.end_block
+                && extensions < Self::max_auction_extensions();
+
+            // 新しい入札額を確保してから、前の最高入札者の入札額を返す。確保した額は精算まで引き出せない。
+            // 自分の入札を引き上げるときは、差額だけを追加で確保する。
+            if raising_own_bid {
+                <balances::Module<T>>::reserve(&sender, amount - auction.highest_bid)?;
+            } else {
+                <balances::Module<T>>::reserve(&sender, amount)?;
+                if let Some(ref previous_bidder) = auction.highest_bidder {
+                    <balances::Module<T>>::unreserve(previous_bidder, auction.highest_bid);
+                }
+            }
+
+            auction.highest_bidder = Some(sender.clone());
+            auction.highest_bid = amount;
+            if extend {
+                auction.end_block = new_end;
+                // 元の終了ブロックの予定は`on_finalise`で`end_block`を見て読み飛ばされる。
+                <AuctionsExpiringAt<T>>::mutate(new_end, |kitties| kitties.push(kitty_id));
+                <AuctionExtensions<T>>::insert(kitty_id, extensions + 1);
+            }
+            <Auctions<T>>::insert(kitty_id, auction);
+
+            // 入札を記録する。`MAX_BID_HISTORY`件を超えたら古いものから捨てる。
+            <BidHistory<T>>::mutate(kitty_id, |history| {
+                history.push((sender.clone(), amount, now));
+                if history.len() > MAX_BID_HISTORY {
+                    let excess = history.len() - MAX_BID_HISTORY;
+                    history.drain(..excess);
+                }
+            });
+
+            Self::deposit_event(RawEvent::BidPlaced(sender, kitty_id, amount));
+            if extend {
+                Self::deposit_event(RawEvent::AuctionExtended(kitty_id, new_end));
+            }
+
+            Ok(())
+        }
+
+        // 即決価格でオークション中のkittyを買い、オークションをすぐに終える関数。
+        // 即決価格以上の入札がすでにあるときは買えない（通常どおり精算される）。
+        fn buy_now(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+            Self::_ensure_market_enabled()?;
+
+            // Verify first, write lastの原則：オークションが開催中で、即決価格が設定されていることを確認する。
+            let auction = Self::auction(kitty_id).ok_or("Error: this kitty is not on an auction")?;
+            ensure!(<system::Module<T>>::block_number() < auction.end_block, "Error: this auction has already ended");
+            let price = Self::auction_buy_now(kitty_id).ok_or("Error: this auction has no buy-now price")?;
+            ensure!(auction.highest_bidder.is_none() || auction.highest_bid < price, "Error: a bid has already reached the buy-now price");
+
+            // Verify first, write lastの原則：出品者がまだ所有者で、kittyを受け取れることを確認する。
+            let owner = Self::_existing_owner_of(kitty_id)?;
+            ensure!(owner == auction.seller, "Error: the seller of this auction no longer owns the kitty");
+            Self::_ensure_not_leased(kitty_id)?;
+            ensure!(owner != sender, Error::BuyOwnKitty.as_str());
+            Self::_ensure_can_transfer_locked(&owner, &sender, kitty_id, LockReason::Auction)?;
+
+            // Verify first, write lastの原則：自分の入札を返してもらってから払う場合も含め、即決価格を払えることを確認する。
+            let raising_own_bid = auction.highest_bidder.as_ref() == Some(&sender);
+            let available = if raising_own_bid {
+                <balances::Module<T>>::free_balance(&sender).saturating_add(auction.highest_bid)
+            } else {
+                <balances::Module<T>>::free_balance(&sender)
+            };
+
+            // Verify first, write lastの原則：販売手数料とロイヤリティを含め、代金を分けて払えることを確認する。
+            let split = Self::_sale_split(kitty_id, &owner, price)?;
+            Self::_ensure_can_pay_sale(&sender, available, &owner, price, &split)?;
+
+            // 最高入札者の入札額を返し、即決価格を出品者とトレジャリー、作者へ払う。
+            if let Some(ref bidder) = auction.highest_bidder {
+                <balances::Module<T>>::unreserve(bidder, auction.highest_bid);
+            }
+            Self::_pay_sale(&sender, &owner, kitty_id, split)?;
+
+            // オークションを終えてロックを外し、kittyを出品者から購入者へ転送する。
+            Self::_close_auction(kitty_id, &owner);
+            Self::_transfer_from(owner.clone(), sender.clone(), kitty_id)?;
+
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+            Self::_record_sale(kitty_id, &sender, &owner, price);
+            Self::deposit_event(RawEvent::AuctionSettled(owner.clone(), sender.clone(), kitty_id, price));
+            Self::deposit_event(RawEvent::Bought(sender, owner, kitty_id, price, Self::total_volume()));
+
+            Ok(())
+        }
+
+        // 終了ブロックを迎えたオークションを精算する関数。誰でも呼べる。
+        fn settle_auction(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let auction = Self::auction(kitty_id).ok_or("Error: this kitty is not on an auction")?;
+            ensure!(<system::Module<T>>::block_number() >= auction.end_block, "Error: this auction has not ended yet");
+
+            Self::_settle_auction(kitty_id, auction);
+
+            Ok(())
+        }
+
+        // 入札がないうちに出品者がオークションを取り下げる関数。
+        fn cancel_auction(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let auction = Self::auction(kitty_id).ok_or("Error: this kitty is not on an auction")?;
+            ensure!(auction.seller == sender, "Error: you are not the seller of this auction");
+            ensure!(auction.highest_bidder.is_none(), "Error: you can not cancel an auction which already has bids");
+
+            Self::_close_auction(kitty_id, &sender);
+
+            Self::deposit_event(RawEvent::AuctionCancelled(sender, kitty_id));
+
+            Ok(())
+        }
+
+        // 自分のkitty`my_kitty`と他人のkitty`their_kitty`の交換を提案する関数。
+        fn propose_swap(origin, my_kitty: T::Hash, their_kitty: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：自分のkittyと他人のkittyであることを確認する。
+            Self::ensure_owner(&sender, my_kitty)?;
+            Self::_ensure_not_leased(my_kitty)?;
+            let counterparty = Self::_existing_owner_of(their_kitty)?;
+            ensure!(counterparty != sender, "Error: you can not swap kitties with yourself");
+
+            let expiry = <system::Module<T>>::block_number().checked_add(&<T::BlockNumber as As<u64>>::sa(SWAP_PROPOSAL_PERIOD))
+                .ok_or(Error::Overflow)?;
+            let proposal = SwapProposal {
+                proposer: sender.clone(),
+                counterparty: counterparty.clone(),
+                expiry,
+            };
+            <SwapProposals<T>>::insert((my_kitty, their_kitty), proposal);
+
+            Self::deposit_event(RawEvent::SwapProposed(sender, my_kitty, counterparty, their_kitty, expiry));
+
+            Ok(())
+        }
+
+        // 相手側kittyの所有者が交換の提案を受け入れる関数。`their_kitty`は提案者のkitty、`my_kitty`は自分のkittyである。
+        fn accept_swap(origin, their_kitty: T::Hash, my_kitty: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：提案が存在し、期限内であることを確認する。
+            let proposal = Self::swap_proposal((their_kitty, my_kitty)).ok_or("Error: there is no such swap proposal")?;
+            ensure!(<system::Module<T>>::block_number() <= proposal.expiry, "Error: this swap proposal has expired");
+            ensure!(proposal.counterparty == sender, "Error: this swap proposal is not addressed to you");
+
+            // Verify first, write lastの原則：提案後にどちらのkittyも所有者が変わっていないことを確認する。
+            let their_owner = Self::_existing_owner_of(their_kitty)?;
+            ensure!(their_owner == proposal.proposer, "Error: the proposer no longer owns the offered kitty");
+            Self::_ensure_not_leased(their_kitty)?;
+            let my_owner = Self::_existing_owner_of(my_kitty)?;
+            ensure!(my_owner == sender, "Error: you no longer own the requested kitty");
+            Self::_ensure_not_leased(my_kitty)?;
+
+            // Verify first, write lastの原則：片方だけ転送されないよう、どちらもロックされていないことを確認する。
+            Self::_ensure_unlocked(their_kitty)?;
+            Self::_ensure_unlocked(my_kitty)?;
+
+            // 交換を実施する。
+            Self::_transfer_from(their_owner.clone(), sender.clone(), their_kitty)?;
+            Self::_transfer_from(sender.clone(), their_owner.clone(), my_kitty)?;
+
+            <SwapProposals<T>>::remove((their_kitty, my_kitty));
+
+            Self::deposit_event(RawEvent::SwapExecuted(their_owner, their_kitty, sender, my_kitty));
+
+            Ok(())
+        }
+
+        // 提案者が交換の提案を取り下げる関数。
+        fn cancel_swap(origin, my_kitty: T::Hash, their_kitty: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let proposal = Self::swap_proposal((my_kitty, their_kitty)).ok_or("Error: there is no such swap proposal")?;
+            ensure!(proposal.proposer == sender, "Error: you are not the proposer of this swap");
+
+            <SwapProposals<T>>::remove((my_kitty, their_kitty));
+
+            Self::deposit_event(RawEvent::SwapCancelled(sender, my_kitty, their_kitty));
+
+            Ok(())
+        }
+
+        // 自分のkittyを`to`が`required_payment`を支払うことを条件に渡すことを提案する関数。
+        fn propose_conditional_transfer(origin, kitty_id: T::Hash, to: T::AccountId, required_payment: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：渡したいkittyの所有者であることを確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            ensure!(to != sender, Error::SelfTransfer.as_str());
+            Self::_ensure_unlocked(kitty_id)?;
+
+            let deadline = <system::Module<T>>::block_number().checked_add(&<T::BlockNumber as As<u64>>::sa(SWAP_PROPOSAL_PERIOD))
+                .ok_or(Error::Overflow)?;
+            <PendingSwaps<T>>::insert(kitty_id, (sender.clone(), to.clone(), required_payment, deadline));
+            Self::lock_kitty(kitty_id, LockReason::ConditionalTransfer)?;
+
+            Self::deposit_event(RawEvent::ConditionalTransferProposed(sender, to, kitty_id, required_payment, deadline));
+
+            Ok(())
+        }
+
+        // 提案された代金を支払ってkittyを受け取る関数。支払いとkittyの転送は同時に行われる。
+        fn accept_conditional_transfer(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：提案が自分宛てで期限内であり、提案者がまだ所有者であることを確認する。
+            let (proposer, to, required_payment, deadline) = Self::pending_swap(kitty_id)
+                .ok_or("Error: there is no conditional transfer proposed for this kitty")?;
+            ensure!(to == sender, "Error: this conditional transfer is not addressed to you");
+            ensure!(<system::Module<T>>::block_number() <= deadline, "Error: this conditional transfer has expired");
+            let owner = Self::_existing_owner_of(kitty_id)?;
+            ensure!(owner == proposer, "Error: the proposer no longer owns this kitty");
+            Self::_ensure_not_leased(kitty_id)?;
+
+            // Verify first, write lastの原則：支払いの後にkittyの転送が失敗しないよう、ロックを外せば転送できることを確認する。
+            Self::_ensure_can_transfer_locked(&proposer, &sender, kitty_id, LockReason::ConditionalTransfer)?;
+            let split = Self::_sale_split(kitty_id, &proposer, required_payment)?;
+            Self::_ensure_can_pay_sale(&sender, <balances::Module<T>>::free_balance(&sender), &proposer, required_payment, &split)?;
+
+            // 代金を販売手数料とロイヤリティに分けて支払う。
+            Self::_pay_sale(&sender, &proposer, kitty_id, split)?;
+
+            // ロックを外してkittyを受け取る。売買なので出品は取り下げる。
+            Self::unlock_kitty(kitty_id);
+            Self::_transfer_from(proposer.clone(), sender.clone(), kitty_id)?;
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+
+            <PendingSwaps<T>>::remove(kitty_id);
+
+            Self::deposit_event(RawEvent::ConditionalTransferAccepted(proposer, sender, kitty_id, required_payment));
+
+            Ok(())
+        }
+
+        // 提案者が条件付き転送の提案を取り下げる関数。
+        fn cancel_conditional_transfer(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let (proposer, _, _, _) = Self::pending_swap(kitty_id)
+                .ok_or("Error: there is no conditional transfer proposed for this kitty")?;
+            ensure!(proposer == sender, "Error: you are not the proposer of this conditional transfer");
+
+            <PendingSwaps<T>>::remove(kitty_id);
+            Self::unlock_kitty(kitty_id);
+
+            Self::deposit_event(RawEvent::ConditionalTransferCancelled(sender, kitty_id));
+
+            Ok(())
+        }
+
+        // 自分のkittyを`to`に`duration`ブロックの間、賃料`rent`で貸し出すことを申し出る関数。
+        // 借り手が`accept_lease`で受け入れるまでkittyも賃料も動かない。
+        fn lease_kitty(origin, to: T::AccountId, kitty_id: T::Hash, duration: T::BlockNumber, rent: T::Balance) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：貸し出したいkittyの所有者であることを確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            ensure!(to != sender, "Error: you can not lease a kitty to yourself");
+            ensure!(!duration.is_zero(), "Error: the duration of a lease must be positive");
+
+            <LeaseOffers<T>>::insert(kitty_id, LeaseOffer { lessee: to.clone(), duration, rent });
+
+            Self::deposit_event(RawEvent::LeaseOffered(sender, to, kitty_id, duration, rent));
+
+            Ok(())
+        }
+
+        // 借り手が貸し出しの申し出を受け入れ、賃料を前払いしてkittyを預かる関数。
+        fn accept_lease(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：申し出が自分宛てで、貸し手がまだ所有者であることを確認する。
+            let offer = Self::lease_offer(kitty_id).ok_or("Error: there is no lease offer for this kitty")?;
+            ensure!(offer.lessee == sender, "Error: this lease offer is not addressed to you");
+            let lessor = Self::_existing_owner_of(kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            ensure!(!<DutchAuctions<T>>::exists(kitty_id), "Error: this kitty is on a dutch auction");
+            ensure!(!<Auctions<T>>::exists(kitty_id), "Error: this kitty is on an auction");
+            Self::_ensure_can_transfer(&lessor, &sender, kitty_id)?;
+
+            let expiry = <system::Module<T>>::block_number().checked_add(&offer.duration).ok_or(Error::Overflow)?;
+
+            // Verify first, write lastの原則：賃料を払えて、貸し手が受け取れることを確認する。
+            ensure!(<balances::Module<T>>::free_balance(&sender) >= offer.rent, "Error: you do not have enough free balance to pay the rent");
+            if !offer.rent.is_zero() {
+                Self::_ensure_can_receive(&lessor, offer.rent)?;
+            }
+
+            // 賃料を前払いする。
+            <balances::Module<T>>::make_transfer(&sender, &lessor, offer.rent)?;
+
+            // kittyを借り手へ転送する。貸し出し中は売りに出さない。
+            Self::_transfer_from(lessor.clone(), sender.clone(), kitty_id)?;
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+
+            <LeaseOffers<T>>::remove(kitty_id);
+            <Leases<T>>::insert(kitty_id, Lease {
+                lessor: lessor.clone(),
+                lessee: sender.clone(),
+                rent: offer.rent,
+                expiry,
+                end_requested_by: None,
+            });
+            <LeasesExpiringAt<T>>::mutate(expiry, |kitties| kitties.push(kitty_id));
+            // ロックされていないことは確認済みなので、そのままロックを掛ける。
+            <KittyLocks<T>>::insert(kitty_id, LockReason::Lease);
+
+            Self::deposit_event(RawEvent::LeaseStarted(lessor, sender, kitty_id, expiry, offer.rent));
+
+            Ok(())
+        }
+
+        // 期限前に貸し出しを終了することに同意する関数。貸し手と借り手の双方が呼ぶとkittyが貸し手へ戻る。
+        // 前払いの賃料は返金しない。
+        fn terminate_lease(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let mut lease = Self::lease(kitty_id).ok_or("Error: this kitty is not leased")?;
+            ensure!(sender == lease.lessor || sender == lease.lessee, "Error: you are not a party to this lease");
+
+            // 相手がすでに同意していれば終了する。そうでなければ自分の同意を記録する。
+            let other_party_agreed = lease.end_requested_by.as_ref().map_or(false, |requester| *requester != sender);
+            if other_party_agreed {
+                Self::_end_lease(kitty_id, lease)?;
+            } else {
+                lease.end_requested_by = Some(sender);
+                <Leases<T>>::insert(kitty_id, lease);
+            }
+
+            Ok(())
+        }
+
+        // 期限を過ぎても返却されていない貸し出しを、貸し手が終了させてkittyを取り戻す関数。
+        // ブロックの終わりの返却が失敗したときに使う。
+        fn reclaim_lease(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let lease = Self::lease(kitty_id).ok_or("Error: this kitty is not leased")?;
+            ensure!(sender == lease.lessor, "Error: only the lessor can reclaim a leased kitty");
+            ensure!(<system::Module<T>>::block_number() >= lease.expiry, "Error: this lease has not expired yet");
+
+            Self::_end_lease(kitty_id, lease)
+        }
+
+        // 自分のkittyを`total_shares`個の持ち分に分割する関数。分割中のkittyはロックされ、丸ごとは転送できない。
+        // 持ち分はすべて分割した人が持つ。
+        fn fractionalize(origin, kitty_id: T::Hash, total_shares: u64) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：分割したいkittyの所有者で、ロックされていないことを確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+            ensure!(total_shares > 0, "Error: a kitty must be split into at least one share");
+
+            // 売りに出したまま分割されないよう出品を取り下げる。
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+            <TotalShares<T>>::insert(kitty_id, total_shares);
+            <KittyShares<T>>::insert((kitty_id, sender.clone()), total_shares);
+            Self::lock_kitty(kitty_id, LockReason::Fractionalized)?;
+
+            Self::deposit_event(RawEvent::Fractionalized(sender, kitty_id, total_shares));
+
+            Ok(())
+        }
+
+        // 分割されたkittyの持ち分を`amount`個`to`へ移す関数。
+        fn transfer_shares(origin, kitty_id: T::Hash, to: T::AccountId, amount: u64) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：分割されたkittyで、移す持ち分を持っていることを確認する。
+            ensure!(<TotalShares<T>>::exists(kitty_id), "Error: this kitty is not split into shares");
+            ensure!(amount > 0, "Error: you can not transfer zero shares");
+            ensure!(to != sender, "Error: you can not transfer shares to yourself");
+            let sender_shares = Self::kitty_shares((kitty_id, sender.clone()));
+            let new_sender_shares = sender_shares.checked_sub(amount)
+                .ok_or("Error: you do not have enough shares of this kitty")?;
+            let new_to_shares = Self::kitty_shares((kitty_id, to.clone())).checked_add(amount)
+                .ok_or(Error::Overflow)?;
+
+            if new_sender_shares == 0 {
+                <KittyShares<T>>::remove((kitty_id, sender.clone()));
+            } else {
+                <KittyShares<T>>::insert((kitty_id, sender.clone()), new_sender_shares);
+            }
+            <KittyShares<T>>::insert((kitty_id, to.clone()), new_to_shares);
+
+            Self::deposit_event(RawEvent::SharesTransferred(sender, to, kitty_id, amount));
+
+            Ok(())
+        }
+
+        // 全持ち分を集めたアカウントが分割を解いて、kittyを単独で所有する関数。
+        fn defractionalize(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：全持ち分を持っていることを確認する。
+            ensure!(<TotalShares<T>>::exists(kitty_id), "Error: this kitty is not split into shares");
+            ensure!(Self::kitty_shares((kitty_id, sender.clone())) == Self::total_shares(kitty_id), "Error: you need all the shares of this kitty to reunify it");
+            let owner = Self::_existing_owner_of(kitty_id)?;
+
+            // ロックを外し、必要なら通常の転送で所有者の記録を移す。転送できなければロックを掛け直す。
+            Self::unlock_kitty(kitty_id);
+            if owner != sender {
+                if let Err(e) = Self::_transfer_from(owner, sender.clone(), kitty_id) {
+                    <KittyLocks<T>>::insert(kitty_id, LockReason::Fractionalized);
+                    return Err(e);
+                }
+            }
+            <TotalShares<T>>::remove(kitty_id);
+            <KittyShares<T>>::remove((kitty_id, sender.clone()));
+
+            Self::deposit_event(RawEvent::Reunified(sender, kitty_id));
+
+            Ok(())
+        }
+
+        // 自分のkittyを担保に`amount`を借りることを申し出る関数。担保のkittyはロックされ、返済するまで転送も出品もできない。
+        fn request_loan(origin, kitty_id: T::Hash, amount: T::Balance, interest: T::Balance, duration: T::BlockNumber) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：担保にしたいkittyの所有者で、ロックされていないことを確認する。
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+            ensure!(!amount.is_zero(), "Error: the amount of a loan must be positive");
+            ensure!(!duration.is_zero(), "Error: the duration of a loan must be positive");
+            amount.checked_add(&interest).ok_or(Error::Overflow)?;
+
+            // 担保の間は売らないので出品を取り下げる。
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+            <Loans<T>>::insert(kitty_id, Loan {
+                borrower: sender.clone(),
+                lender: None,
+                amount,
+                interest,
+                duration,
+                due: None,
+            });
+            Self::lock_kitty(kitty_id, LockReason::Collateral)?;
+
+            Self::deposit_event(RawEvent::LoanRequested(sender, kitty_id, amount, interest, duration));
+
+            Ok(())
+        }
+
+        // 借り手がまだ資金を受け取っていない借り入れの申し出を取り下げる関数。
+        fn cancel_loan_request(origin, kitty_id: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let loan = Self::loan(kitty_id).ok_or("Error: there is no loan for this kitty")?;
+            ensure!(loan.borrower == sender, "Error: you are not the borrower of this loan");
+            ensure!(loan.lender.is_none(), "Error: this loan has already been funded");
+
+            <Loans<T>>::remove(kitty_id);
+            Self::unlock_kitty(kitty_id);
+
+            Self::deposit_event(RawEvent::LoanRequestCancelled(sender, kitty_id));
+
+            Ok(())
+        }
+
+        // 借り入れの申し出に応じて借り手へ元本を送る関数。返済期限は今から`duration`ブロック後になる。
+        fn fund_loan(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：まだ誰も資金を出していない申し出であることを確認する。
+            let mut loan = Self::loan(kitty_id).ok_or("Error: there is no loan for this kitty")?;
+            ensure!(loan.lender.is_none(), "Error: this loan has already been funded");
+            ensure!(loan.borrower != sender, "Error: you can not fund your own loan");
+
+            // Verify first, write lastの原則：元本を送る前に、返済期限を計算でき、借り手が受け取れることを確認する。
+            let due = <system::Module<T>>::block_number().checked_add(&loan.duration).ok_or(Error::Overflow)?;
+            ensure!(<balances::Module<T>>::free_balance(&sender) >= loan.amount, "Error: you do not have enough free balance to fund this loan");
+            Self::_ensure_can_receive(&loan.borrower, loan.amount)?;
+
+            // 元本を借り手へ送る。
+            <balances::Module<T>>::make_transfer(&sender, &loan.borrower, loan.amount)?;
+
+            let borrower = loan.borrower.clone();
+            loan.lender = Some(sender.clone());
+            loan.due = Some(due);
+            <Loans<T>>::insert(kitty_id, loan);
+
+            Self::deposit_event(RawEvent::LoanFunded(sender, borrower, kitty_id, due));
+
+            Ok(())
+        }
+
+        // 借り手が返済期限までに元本と利息を返済し、担保のロックを外す関数。期限のブロックと、その後の猶予の間も返済できる。
+        fn repay_loan(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：資金を受け取った自分の借り入れで、期限内であることを確認する。
+            let loan = Self::loan(kitty_id).ok_or("Error: there is no loan for this kitty")?;
+            ensure!(loan.borrower == sender, "Error: you are not the borrower of this loan");
+            let lender = loan.lender.ok_or("Error: this loan has not been funded yet")?;
+            let due = loan.due.ok_or("Error: this loan has not been funded yet")?;
+            let deadline = due.checked_add(&Self::loan_grace_period()).ok_or(Error::Overflow)?;
+            ensure!(<system::Module<T>>::block_number() <= deadline, "Error: this loan is past its due block");
+            let total = loan.amount.checked_add(&loan.interest)
+                .ok_or(Error::Overflow)?;
+
+            // 元本と利息を貸し手へ返す。
+            <balances::Module<T>>::make_transfer(&sender, &lender, total)?;
+
+            <Loans<T>>::remove(kitty_id);
+            Self::unlock_kitty(kitty_id);
+
+            Self::deposit_event(RawEvent::LoanRepaid(sender, lender, kitty_id, total));
+
+            Ok(())
+        }
+
+        // 返済期限と猶予を過ぎても返済されなかった借り入れの担保を貸し手が受け取る関数。
+        // kittyはロックを外した上で通常の転送で移すので、受け取った貸し手はすぐに出品できる。
+        fn claim_collateral(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：自分が貸し手で、返済期限を過ぎていることを確認する。
+            let loan = Self::loan(kitty_id).ok_or("Error: there is no loan for this kitty")?;
+            ensure!(loan.lender.as_ref() == Some(&sender), "Error: you are not the lender of this loan");
+            let due = loan.due.ok_or("Error: this loan has not been funded yet")?;
+            let deadline = due.checked_add(&Self::loan_grace_period()).ok_or(Error::Overflow)?;
+            ensure!(<system::Module<T>>::block_number() > deadline, "Error: this loan is not past its due block and grace period yet");
+
+            // ロックを外して担保を貸し手へ転送する。転送できなければロックを掛け直す。
+            Self::unlock_kitty(kitty_id);
+            if let Err(e) = Self::_transfer_from(loan.borrower.clone(), sender.clone(), kitty_id) {
+                <KittyLocks<T>>::insert(kitty_id, LockReason::Collateral);
+                return Err(e);
+            }
+            <Loans<T>>::remove(kitty_id);
+
+            Self::deposit_event(RawEvent::CollateralLiquidated(sender, loan.borrower, kitty_id, loan.amount, loan.interest));
+
+            Ok(())
+        }
+
+        // 自分のkittyで他人のkittyに対戦を申し込む関数。賭け金`wager`は対戦が終わるか取り下げるまで確保される。
+        // `expires_in`ブロックが過ぎると受けられなくなり、誰でも`cancel_challenge`で取り下げて賭け金を挑戦者へ返せる。
+        fn challenge(origin, my_kitty: T::Hash, their_kitty: T::Hash, wager: T::Balance, expires_in: T::BlockNumber) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：自分のkittyで、他人のkittyに挑むことを確認する。
+            Self::ensure_owner(&sender, my_kitty)?;
+            let defender_owner = Self::_existing_owner_of(their_kitty)?;
+            ensure!(defender_owner != sender, "Error: you can not challenge your own kitty");
+            ensure!(!<Challenges<T>>::exists(my_kitty), "Error: this kitty has already issued a challenge");
+
+            // Verify first, write lastの原則：期限は未来のブロックでなければならない。
+            ensure!(!expires_in.is_zero(), "Error: the challenge must stay open for at least one block");
+            let expiry = <system::Module<T>>::block_number().checked_add(&expires_in).ok_or(Error::Overflow)?;
+
+            // 賭け金を確保する。
+            if !wager.is_zero() {
+                <balances::Module<T>>::reserve(&sender, wager)?;
+            }
+            <Challenges<T>>::insert(my_kitty, Challenge {
+                challenger: sender.clone(),
+                defender: their_kitty,
+                wager,
+            });
+            <ChallengeExpiry<T>>::insert(my_kitty, expiry);
+
+            Self::deposit_event(RawEvent::ChallengeIssued(sender, my_kitty, their_kitty, wager));
+
+            Ok(())
+        }
+
+        // 受けられていない対戦の申し込みを取り下げ、賭け金を挑戦者へ返す関数。
+        // 挑戦者はいつでも、それ以外の人は期限が過ぎた後に呼べる。
+        fn cancel_challenge(origin, my_kitty: T::Hash) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            let challenge = Self::pending_challenge(my_kitty).ok_or("Error: this kitty has not issued a challenge")?;
+            ensure!(!<AcceptedChallenges<T>>::exists(my_kitty), "Error: this challenge has already been accepted");
+            let expired = Self::challenge_expiry(my_kitty).map_or(false, |expiry| <system::Module<T>>::block_number() > expiry);
+            ensure!(challenge.challenger == sender || expired, "Error: you did not issue this challenge");
+
+            <Challenges<T>>::remove(my_kitty);
+            <ChallengeExpiry<T>>::remove(my_kitty);
+            <balances::Module<T>>::unreserve(&challenge.challenger, challenge.wager);
+
+            Self::deposit_event(RawEvent::ChallengeCancelled(sender, my_kitty));
+
+            Ok(())
+        }
+
+        // 挑まれたkittyの所有者が対戦を受ける関数。同じ額の賭け金を確保し、勝敗は次のブロックの終わりに`_resolve_battle`で決まる。
+        // 受けるトランザクションを送る時点で分かっているのは今のブロックの乱数だけなので、受けた側は結果を見てから受けることはできない。
+        // 次のブロックを作る人は乱数に影響できるので、大きな賭けには予測できない乱数源を`Randomness`に与えること。
+        // どちらのkittyも所有者は変わらず、賭け金だけが勝った側へ移る。
+        fn accept_challenge(origin, challenger_kitty: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：挑まれたkittyの所有者で、挑戦者がまだ挑んだkittyを所有していることを確認する。
+            let challenge = Self::pending_challenge(challenger_kitty).ok_or("Error: this kitty has not issued a challenge")?;
+            ensure!(!<AcceptedChallenges<T>>::exists(challenger_kitty), "Error: this challenge has already been accepted");
+            let now = <system::Module<T>>::block_number();
+            ensure!(Self::challenge_expiry(challenger_kitty).map_or(true, |expiry| now <= expiry), "Error: this challenge has expired");
+            Self::ensure_owner(&sender, challenge.defender)?;
+            let challenger_owner = Self::_existing_owner_of(challenger_kitty)?;
+            ensure!(challenger_owner == challenge.challenger, "Error: the challenger no longer owns the challenging kitty");
+            ensure!(challenger_owner != sender, "Error: you can not accept your own challenge");
+            let resolve_at = now.checked_add(&<T::BlockNumber as As<u64>>::sa(1)).ok_or(Error::Overflow)?;
+
+            // Verify first, write lastの原則：賭け金を確保しても口座が消されないことを確認する。
+            let free_balance = <balances::Module<T>>::free_balance(&sender);
+            ensure!(
+                free_balance.checked_sub(&challenge.wager).map_or(false, |rest| rest >= <balances::Module<T>>::existential_deposit()),
+                "Error: you do not have enough free balance to match the wager"
+            );
+
+            // 負けたときに払えるよう、賭け金を確保する。
+            if !challenge.wager.is_zero() {
+                <balances::Module<T>>::reserve(&sender, challenge.wager)?;
+            }
+            <AcceptedChallenges<T>>::insert(challenger_kitty, &sender);
+            <ChallengeExpiry<T>>::remove(challenger_kitty);
+            <BattlesResolvingAt<T>>::mutate(resolve_at, |kitties| kitties.push(challenger_kitty));
+
+            Self::deposit_event(RawEvent::ChallengeAccepted(sender, challenger_kitty, resolve_at));
+
+            Ok(())
+        }
+
+        // 自分のkittyをすべて`into`へ統合することを提案する関数。`into`が`accept_merge`で受け入れると統合される。
+        fn propose_merge(origin, into: T::AccountId) -> Result {
+
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            ensure!(into != sender, "Error: you can not merge your collection into yourself");
+
+            <MergeProposals<T>>::insert(&sender, &into);
+
+            Self::deposit_event(RawEvent::MergeProposed(sender, into));
+
+            Ok(())
+        }
+
+        // `from`からの統合の提案を受け入れ、`from`のkittyを自分へ移す関数。
+        // ロック中や貸し出し中のkittyは飛ばして`from`に残し、提案もそのまま残すので、ロックが外れた後にもう一度受け入れる。
+        // 1匹も動かせないときだけ失敗する。
+        fn accept_merge(origin, from: T::AccountId) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：双方が統合に同意していることを確認する。
+            let into = Self::merge_proposal(&from).ok_or("Error: this account has not proposed a merge")?;
+            ensure!(into == sender, "Error: this merge proposal is not addressed to you");
+
+            // Verify first, write lastの原則：移すkittyの数が上限以下で、すべて移せることを確認する。
+            let count = Self::owned_kitty_count(&from);
+            ensure!(count <= MAX_MERGE_KITTIES, "Error: this collection is too large to merge at once");
+            let new_count = Self::owned_kitty_count(&sender).checked_add(count)
+                .ok_or(Error::Overflow)?;
+            Self::_ensure_can_own(new_count)?;
+            let kitty_ids = Self::_movable_kitty_ids(&from, count);
+            ensure!(count == 0 || !kitty_ids.is_empty(), Error::NoMovableKitties.as_str());
+            let moved = kitty_ids.len() as u64;
+
+            for kitty_id in kitty_ids {
+                Self::_transfer_from(from.clone(), sender.clone(), kitty_id)?;
+            }
+
+            let remaining = count - moved;
+            if remaining == 0 {
+                <MergeProposals<T>>::remove(&from);
+            }
+
+            Self::deposit_event(RawEvent::CollectionsMerged(from, sender, moved, remaining));
+
+            Ok(())
+        }
+
+        // rootがアカウントごとの呼び出し回数の制限を設定する関数。`max_calls`を0にすると制限をなくす。
+        fn set_rate_limit(origin, max_calls: u32, window: T::BlockNumber) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(max_calls == 0 || !window.is_zero(), "Error: the rate limit window must be positive");
+
+            <MaxCallsPerWindow<T>>::put(max_calls);
+            <RateWindow<T>>::put(window);
+
+            Self::deposit_event(RawEvent::RateLimitSet(max_calls, window));
+
+            Ok(())
+        }
+
+        // rootが返済期限後に担保を受け取れるまでの猶予を設定する関数。資金提供済みの借り入れにもすぐに適用される。
+        fn set_loan_grace_period(origin, grace_period: T::BlockNumber) -> Result {
+
+            ensure_root(origin)?;
+
+            <LoanGracePeriod<T>>::put(grace_period);
+
+            Self::deposit_event(RawEvent::LoanGracePeriodSet(grace_period));
+
+            Ok(())
+        }
+
+        // rootが`create_kitty`を`Minters`のアカウントに限るかを設定する関数。falseにすると誰でも生成できる。
+        fn set_mint_allowlist_enabled(origin, enabled: bool) -> Result {
+
+            ensure_root(origin)?;
+
+            <RestrictMinting<T>>::put(enabled);
+
+            Self::deposit_event(RawEvent::MintAllowlistEnabled(enabled));
+
+            Ok(())
+        }
+
+        // rootが売買を有効にするかを設定する関数。無効にしても出品の取り下げやオークションの精算はできる。
+        fn set_market_enabled(origin, enabled: bool) -> Result {
+
+            ensure_root(origin)?;
+
+            <MarketEnabled<T>>::put(enabled);
+
+            Self::deposit_event(RawEvent::MarketToggled(enabled));
+
+            Ok(())
+        }
+
+        // rootが1ブロックで`create_kitty`により生成できるkittyの数を設定する関数。`max_mints`を0にすると制限をなくす。
+        fn set_max_mints_per_block(origin, max_mints: u32) -> Result {
+
+            ensure_root(origin)?;
+
+            <MaxMintsPerBlock<T>>::put(max_mints);
+
+            Self::deposit_event(RawEvent::MaxMintsPerBlockSet(max_mints));
+
+            Ok(())
+        }
+
+        // rootが1アカウントが`create_kitty`を続けて呼べる間隔を設定する関数。`cooldown`を0にすると制限をなくす。
+        fn set_create_cooldown(origin, cooldown: T::BlockNumber) -> Result {
+
+            ensure_root(origin)?;
+
+            <CreateCooldown<T>>::put(cooldown);
+
+            Self::deposit_event(RawEvent::CreateCooldownSet(cooldown));
+
+            Ok(())
+        }
+
+        // rootが`create_kitty`で1匹ごとに払う手数料を設定する関数。`fee`を0にすると手数料を取らない。
+        fn set_creation_fee(origin, fee: T::Balance) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(fee.is_zero() || Self::treasury().is_some(), Error::NoTreasury.as_str());
+
+            <CreationFee<T>>::put(fee);
+
+            Self::deposit_event(RawEvent::CreationFeeSet(fee));
+
+            Ok(())
+        }
+
+        // rootが1アカウントが所有できるkittyの数を設定する関数。`max_owned`を0にすると制限をなくす。
+        // すでに上限を超えて所有しているアカウントからkittyを取り上げることはしない。
+        fn set_max_kitties_owned(origin, max_owned: u64) -> Result {
+
+            ensure_root(origin)?;
+
+            <MaxKittiesOwned<T>>::put(max_owned);
+
+            Self::deposit_event(RawEvent::MaxKittiesOwnedSet(max_owned));
+
+            Ok(())
+        }
+
+        // rootが`create_kitty`で1匹ごとに確保する預り金を設定する関数。`deposit`を0にすると預り金を取らない。
+        // すでに確保されている預り金は変わらない。
+        fn set_kitty_deposit(origin, deposit: T::Balance) -> Result {
+
+            ensure_root(origin)?;
+
+            <KittyDeposit<T>>::put(deposit);
+
+            Self::deposit_event(RawEvent::KittyDepositSet(deposit));
+
+            Ok(())
+        }
+
+        // rootがステークの報酬を払うアカウントと、1ブロックあたりの報酬を設定する関数。`pot`を`None`にすると報酬を払わない。
+        fn set_staking_reward(origin, pot: Option<T::AccountId>, reward_per_block: T::Balance) -> Result {
+
+            ensure_root(origin)?;
+
+            match pot {
+                Some(ref account) => <RewardPot<T>>::put(account),
+                None => <RewardPot<T>>::kill(),
+            }
+            <RewardPerBlock<T>>::put(reward_per_block);
+
+            Self::deposit_event(RawEvent::StakingRewardSet(pot, reward_per_block));
+
+            Ok(())
+        }
+
+        // 自分のkittyをステークする関数。ステーク中のkittyは転送も出品も交配もできない。
+        fn stake_kitty(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：本当にそのkittyはあなたのもの？他の用途でロックされていない？
+            Self::ensure_owner(&sender, kitty_id)?;
+            Self::_ensure_not_leased(kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+            let new_staked_count = Self::staked_count(&sender).checked_add(1).ok_or(Error::Overflow)?;
+
+            // 売りに出していれば取り下げる。
+            if !Self::price_of(kitty_id).is_zero() {
+                Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+            }
+            Self::lock_kitty(kitty_id, LockReason::Staked)?;
+            <StakedKitties<T>>::insert(kitty_id, (sender.clone(), <system::Module<T>>::block_number()));
+            <StakedCount<T>>::insert(&sender, new_staked_count);
+
+            Self::deposit_event(RawEvent::KittyStaked(sender, kitty_id));
+
+            Ok(())
+        }
+
+        // ステークしていたkittyを引き出し、報酬を受け取る関数。
+        // 原資が足りなければ報酬は払わずにkittyだけを引き出す。
+        fn unstake_kitty(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：本当にあなたがステークしたkitty？
+            let (staker, staked_at) = Self::staked_kitty(kitty_id).ok_or("Error: this kitty is not staked")?;
+            ensure!(staker == sender, "Error: you did not stake this kitty");
+
+            // 報酬はステークしていたブロック数に比例し、世代が若いkittyほど多い。
+            let blocks_staked = <system::Module<T>>::block_number() - staked_at;
+            let gen = Self::kitty(kitty_id).gen;
+            let reward = Self::reward_per_block()
+                .checked_mul(&<T::Balance as As<u64>>::sa(blocks_staked.as_()))
+                .ok_or(Error::Overflow)?
+                / <T::Balance as As<u64>>::sa(gen.saturating_add(1));
+
+            // Verify first, write lastの原則：原資が報酬を払っても存在保証金を下回らず、受け取れる額のときだけ払う。
+            let payer = match Self::reward_pot() {
+                Some(ref pot) if !reward.is_zero() && *pot != sender => {
+                    let remaining = <balances::Module<T>>::free_balance(pot).checked_sub(&reward);
+                    if remaining.map_or(false, |remaining| remaining >= <balances::Module<T>>::existential_deposit())
+                        && Self::_ensure_can_receive(&sender, reward).is_ok()
+                    {
+                        Some(pot.clone())
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+
+            // 報酬の支払いが失敗すればステークは解かない。
+            match payer {
+                Some(pot) => {
+                    <balances::Module<T>>::make_transfer(&pot, &sender, reward)?;
+                    Self::deposit_event(RawEvent::StakingRewardPaid(sender.clone(), kitty_id, reward));
+                }
+                None if !reward.is_zero() => {
+                    Self::deposit_event(RawEvent::StakingRewardUnpaid(sender.clone(), kitty_id, reward));
+                }
+                None => {}
+            }
+
+            Self::unlock_kitty(kitty_id);
+            <StakedKitties<T>>::remove(kitty_id);
+            <StakedCount<T>>::mutate(&sender, |count| *count = count.saturating_sub(1));
+
+            Self::deposit_event(RawEvent::KittyUnstaked(sender, kitty_id, blocks_staked));
+
+            Ok(())
+        }
+
+        // rootが消されたアカウントのkittyを引き取るアカウントを設定する関数。`None`にすると引き取らない。
+        fn set_pound(origin, pound: Option<T::AccountId>) -> Result {
+
+            ensure_root(origin)?;
+
+            match pound {
+                Some(ref account) => <Pound<T>>::put(account),
+                None => <Pound<T>>::kill(),
+            }
+
+            Self::deposit_event(RawEvent::PoundSet(pound));
+
+            Ok(())
+        }
+
+        // 消されたアカウントに残っているkittyを、最大`MAX_IMPOUND_BATCH`匹`Pound`へ移す関数。誰でも呼べる。
+        fn rescue_kitties(origin, who: T::AccountId) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：`who`が消されたままで、引き取り先があることを確認する。
+            ensure!(Self::is_reaped(&who), "Error: this account has no kitties left to rescue");
+            ensure!(<balances::Module<T>>::free_balance(&who).is_zero(), "Error: this account is alive again and keeps its kitties");
+            let pound = Self::pound().ok_or("Error: there is no pound to take the kitties")?;
+
+            Self::_impound(&who, &pound);
+
+            Ok(())
+        }
+
+        // 自分のkittyを削除せずに手放し、`Pound`へ移す関数。出品は取り下げ、誰でも引き取れるようにする。
+        fn surrender_kitty(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：本当にそのkittyはあなたのもの？手放す先はある？
+            Self::ensure_owner(&sender, kitty_id)?;
+            let pound = Self::pound().ok_or("Error: there is no pound to take the kitty")?;
+            ensure!(sender != pound, "Error: the pound can not surrender its own kitties");
+            Self::_ensure_not_leased(kitty_id)?;
+            Self::_ensure_unlocked(kitty_id)?;
+
+            // 出品と最低価格を取り下げてから移す。
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+            <PriceFloor<T>>::remove(kitty_id);
+            <Self as NonFungible>::transfer(sender.clone(), pound, kitty_id)?;
+            <Adoptable<T>>::insert(kitty_id, true);
+
+            Self::deposit_event(RawEvent::Surrendered(sender, kitty_id));
+
+            Ok(())
+        }
+
+        // `Pound`で引き取り手を待っているkittyを、手数料を払って引き取る関数。
+        fn adopt_kitty(origin, kitty_id: T::Hash) -> Result {
+
+            // Verify first, write lastの原則：正当なユーザーがこの関数を叩いたかを確認する。
+            let sender = ensure_signed(origin)?;
+            Self::_check_rate_limit(&sender)?;
+
+            // Verify first, write lastの原則：kittyが引き取り手を待っていて、まだ`Pound`にいることを確認する。
+            ensure!(Self::is_adoptable(kitty_id), "Error: this kitty is not waiting for adoption");
+            let pound = Self::pound().ok_or("Error: there is no pound to adopt from")?;
+            ensure!(Self::_existing_owner_of(kitty_id)? == pound, "Error: this kitty is no longer in the pound");
+            ensure!(sender != pound, Error::SelfTransfer.as_str());
+
+            // Verify first, write lastの原則：kittyを動かす前に、手数料を払えることを確認する。
+            let fee = Self::adoption_fee();
+            let treasury = if fee.is_zero() {
+                None
+            } else {
+                let treasury = Self::treasury().ok_or(Error::NoTreasury)?;
+                Self::_ensure_can_receive(&treasury, fee)?;
+                Some(treasury)
+            };
+            Self::_ensure_remains_above_existential_deposit(&sender, fee)?;
+
+            // Verify first, write lastの原則：手数料を払う前に、kittyを引き取れることを確認する。
+            Self::_ensure_not_leased(kitty_id)?;
+            Self::_ensure_can_transfer(&pound, &sender, kitty_id)?;
+
+            <Self as NonFungible>::transfer(pound, sender.clone(), kitty_id)?;
+            <Adoptable<T>>::remove(kitty_id);
+            if let Some(treasury) = treasury {
+                <balances::Module<T>>::make_transfer(&sender, &treasury, fee)?;
+            }
+
+            Self::deposit_event(RawEvent::Adopted(sender, kitty_id, fee));
+
+            Ok(())
+        }
+
+        // rootが`Pound`のkittyを引き取るときの手数料を設定する関数。
+        fn set_adoption_fee(origin, fee: T::Balance) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(fee.is_zero() || Self::treasury().is_some(), Error::NoTreasury.as_str());
+
+            <AdoptionFee<T>>::put(fee);
+
+            Self::deposit_event(RawEvent::AdoptionFeeSet(fee));
+
+            Ok(())
+        }
+
+        // rootが`who`を`create_kitty`を呼べるアカウントに加える関数。
+        fn add_minter(origin, who: T::AccountId) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(!Self::is_minter(&who), "Error: this account is already a minter");
+
+            <Minters<T>>::insert(&who, true);
+
+            Self::deposit_event(RawEvent::MinterAdded(who));
+
+            Ok(())
+        }
+
+        // rootが`who`を`create_kitty`を呼べるアカウントから外す関数。
+        fn remove_minter(origin, who: T::AccountId) -> Result {
+
+            ensure_root(origin)?;
+            ensure!(Self::is_minter(&who), "Error: this account is not a minter");
+
+            <Minters<T>>::remove(&who);
+
+            Self::deposit_event(RawEvent::MinterRemoved(who));
+
+            Ok(())
+        }
+
+        // rootが1アカウントで同時に開催できるオークションの数を設定する関数。`max_auctions`を0にすると制限をなくす。
+        // 上限を下げても開催中のオークションはそのまま続く。
+        fn set_max_auctions_per_account(origin, max_auctions: u32) -> Result {
+
+            ensure_root(origin)?;
+
+            <MaxAuctionsPerAccount<T>>::put(max_auctions);
+
+            Self::deposit_event(RawEvent::MaxAuctionsPerAccountSet(max_auctions));
+
+            Ok(())
+        }
+
+        // rootが精算したオークションの入札の記録を残すブロック数を設定する関数。0なら精算したブロックの終わりに消す。
+        fn set_bid_history_retention(origin, retention: T::BlockNumber) -> Result {
+
+            ensure_root(origin)?;
+
+            <BidHistoryRetention<T>>::put(retention);
+
+            Self::deposit_event(RawEvent::BidHistoryRetentionSet(retention));
+
+            Ok(())
+        }
+
+        // rootが終了間際の入札によるオークションの延長を設定する関数。`window`を0にすると延長しない。
+        // 終了まで`window`ブロックを切った入札で終了ブロックを入札ブロック+`window`まで延ばし、延長は`max_extensions`回までとする。
+        fn set_auction_extension(origin, window: T::BlockNumber, max_extensions: u32) -> Result {
+
+            ensure_root(origin)?;
+
+            <AuctionExtensionWindow<T>>::put(window);
+            <MaxAuctionExtensions<T>>::put(max_extensions);
+
+            Self::deposit_event(RawEvent::AuctionExtensionSet(window, max_extensions));
+
+            Ok(())
+        }
+
+        // ブロックの終わりに期限を迎えた貸し出しを終了し、kittyを貸し手へ戻す。
+        // 終了ブロックを迎えたオークションも精算する。1ブロックで精算する件数には上限を設け、残りは次のブロックに回す。
+        fn on_finalise(n: T::BlockNumber) {
+            for kitty_id in <LeasesExpiringAt<T>>::take(n) {
+                if let Some(lease) = Self::lease(kitty_id) {
+                    // 期限前に終了した後に再度貸し出されたkittyは、新しい期限まで待つ。
+                    // 返却できなかった貸し出しは残り、貸し手が`reclaim_lease`で取り戻す。
+                    if lease.expiry == n {
+                        let lessor = lease.lessor.clone();
+                        if Self::_end_lease(kitty_id, lease).is_err() {
+                            Self::deposit_event(RawEvent::LeaseReturnFailed(lessor, kitty_id));
+                        }
+                    }
+                }
+            }
+
+            let mut expiring_auctions = <AuctionsExpiringAt<T>>::take(n);
+            if expiring_auctions.len() > MAX_AUCTION_SETTLEMENTS_PER_BLOCK {
+                let remainder = expiring_auctions.split_off(MAX_AUCTION_SETTLEMENTS_PER_BLOCK);
+                let next = n.saturating_add(<T::BlockNumber as As<u64>>::sa(1));
+                <AuctionsExpiringAt<T>>::mutate(next, |kitties| kitties.extend(remainder));
+            }
+            for kitty_id in expiring_auctions {
+                // 手動で精算済みのオークションは残っていない。
+                if let Some(auction) = Self::auction(kitty_id) {
+                    if auction.end_block <= n {
+                        Self::_settle_auction(kitty_id, auction);
+                    }
+                }
+            }
+
+            // 保存期間が過ぎた入札の記録を消す。同じkittyの新しいオークションが始まっていれば残す。
+            let mut expiring_histories = <BidHistoriesExpiringAt<T>>::take(n);
+            if expiring_histories.len() > MAX_AUCTION_SETTLEMENTS_PER_BLOCK {
+                let remainder = expiring_histories.split_off(MAX_AUCTION_SETTLEMENTS_PER_BLOCK);
+                let next = n.saturating_add(<T::BlockNumber as As<u64>>::sa(1));
+                <BidHistoriesExpiringAt<T>>::mutate(next, |kitties| kitties.extend(remainder));
+            }
+            for kitty_id in expiring_histories {
+                if !<Auctions<T>>::exists(kitty_id) {
+                    <BidHistory<T>>::remove(kitty_id);
+                }
+            }
+
+            // 期限が過ぎた出品を取り下げる。多すぎる分は次のブロックに回す。
+            let mut expiring_listings = <ListingsExpiringAt<T>>::take(n);
+            if expiring_listings.len() > MAX_LISTING_EXPIRIES_PER_BLOCK {
+                let remainder = expiring_listings.split_off(MAX_LISTING_EXPIRIES_PER_BLOCK);
+                let next = n.saturating_add(<T::BlockNumber as As<u64>>::sa(1));
+                <ListingsExpiringAt<T>>::mutate(next, |kitties| kitties.extend(remainder));
+            }
+            for kitty_id in expiring_listings {
+                // 出品し直したkittyは、新しい期限まで待つ。
+                if Self::listing_expiry(kitty_id).map_or(false, |expiry| expiry <= n) {
+                    Self::_set_price(kitty_id, Zero::zero());
+                    Self::deposit_event(RawEvent::ListingExpired(kitty_id));
+                }
+            }
+
+            // 受けられた対戦の勝敗を決める。多すぎる分は次のブロックに回す。
+            let mut battles = <BattlesResolvingAt<T>>::take(n);
+            if battles.len() > MAX_BATTLES_PER_BLOCK {
+                let remainder = battles.split_off(MAX_BATTLES_PER_BLOCK);
+                let next = n.saturating_add(<T::BlockNumber as As<u64>>::sa(1));
+                <BattlesResolvingAt<T>>::mutate(next, |kitties| kitties.extend(remainder));
+            }
+            for challenger_kitty in battles {
+                Self::_resolve_battle(challenger_kitty);
+            }
+
+            // 順番待ちの交配を先頭から`MAX_BREEDS_PER_BLOCK`件まで行う。条件を満たさなくなった申し込みは飛ばす。
+            let mut queue = <BreedQueue<T>>::take();
+            if !queue.is_empty() {
+                let remainder = queue.split_off(cmp::min(queue.len(), MAX_BREEDS_PER_BLOCK));
+                for (who, kitty_id_1, kitty_id_2) in queue {
+                    if Self::_breed(who.clone(), kitty_id_1, kitty_id_2, 1).is_err() {
+                        Self::deposit_event(RawEvent::BreedSkipped(who, kitty_id_1, kitty_id_2));
+                    }
+                }
+                if !remainder.is_empty() {
+                    <BreedQueue<T>>::put(remainder);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Trait> NonFungible for Module<T> {
+    type ItemId = T::Hash;
+    type AccountId = T::AccountId;
+
+    fn owner(id: T::Hash) -> Option<T::AccountId> {
+        Self::_existing_owner_of(id).ok()
+    }
+
+    // 貸し出し中のkittyは借り手のものではないので動かせない。ロックなどの確認は`_transfer_from`が行う。
+    fn transfer(from: T::AccountId, to: T::AccountId, id: T::Hash) -> Result {
+        Self::_ensure_not_leased(id)?;
+        Self::_transfer_from(from, to, id)
+    }
+
+    // 第0世代のkittyとして生成する。遺伝子は`dna`から導く。
+    fn mint(to: T::AccountId, id: T::Hash, dna: T::Hash) -> Result {
+        let new_kitty = Kitty {
+            id,
+            dna,
+            price: <T::Balance as As<u64>>::sa(0),
+            gen: 0,
+        };
+        Self::_mint(to, id, new_kitty, Self::_expand_genome(dna), KittyOrigin::External)
+    }
+
+    fn burn(id: T::Hash) -> Result {
+        let owner = Self::_existing_owner_of(id)?;
+        Self::_burn(owner, id)
+    }
+}
+
+// 残高が0になってアカウントが消されたら、そのkittyを`Pound`へ移す。
+// 一度に移すのは`MAX_IMPOUND_BATCH`匹までで、残りは`rescue_kitties`で引き取る。
+impl<T: Trait> OnFreeBalanceZero<T::AccountId> for Module<T> {
+    fn on_free_balance_zero(who: &T::AccountId) {
+        if Self::owned_kitty_count(who) == 0 {
+            return;
+        }
+
+        <Reaped<T>>::insert(who, true);
+        if let Some(pound) = Self::pound() {
+            Self::_impound(who, &pound);
+        }
+    }
+}
+
+impl <T: Trait> Module<T> {
+
+    // `account`の`index`番目のkittyを返す。互換性のために残しているビューで、連結リストを先頭から`index`個たどる。
+    pub fn kitty_of_owner_by_index(key: (T::AccountId, u64)) -> T::Hash {
+        let (account, index) = key;
+        Self::_owned_kitty_ids(&account, index, 1).pop().unwrap_or_default()
+    }
+
+    // `account`の連結リストを先頭からたどり、`start`番目から最大`limit`匹のハッシュ値を返す。
+    fn _owned_kitty_ids(account: &T::AccountId, start: u64, limit: u64) -> Vec<T::Hash> {
+        let mut kitty_ids = Vec::new();
+        let mut cursor = Self::owned_kitties_list((account.clone(), None)).and_then(|head| head.next);
+        let mut index = 0;
+        while let Some(kitty_id) = cursor {
+            if kitty_ids.len() as u64 >= limit {
+                break;
+            }
+            if index >= start {
+                kitty_ids.push(kitty_id);
+            }
+            index += 1;
+            cursor = Self::owned_kitties_list((account.clone(), Some(kitty_id))).and_then(|item| item.next);
+        }
+
+        kitty_ids
+    }
+
+    // `account`の連結リストを先頭から最大`limit`匹たどり、ロック中でも貸し出し中でもないkittyのハッシュ値を返す。
+    fn _movable_kitty_ids(account: &T::AccountId, limit: u64) -> Vec<T::Hash> {
+        Self::_owned_kitty_ids(account, 0, limit)
+            .into_iter()
+            .filter(|kitty_id| Self::_ensure_not_leased(*kitty_id).is_ok() && Self::_ensure_unlocked(*kitty_id).is_ok())
+            .collect()
+    }
+
+    // kittyが所有者にとって何番目かを、連結リストを先頭からたどって求める。
+    fn _owner_index_of(owner: &T::AccountId, kitty_id: T::Hash) -> u64 {
+        let mut index = 0;
+        let mut cursor = Self::owned_kitties_list((owner.clone(), None)).and_then(|head| head.next);
+        while let Some(current) = cursor {
+            if current == kitty_id {
+                break;
+            }
+            index += 1;
+            cursor = Self::owned_kitties_list((owner.clone(), Some(current))).and_then(|item| item.next);
+        }
+
+        index
+    }
+
+    // 全kittyの`start`番目から最大`limit`匹を、ハッシュ値とkittyの組で返す。通し番号を管理していなければ空である。
+    // 範囲は`all_kitties_count`と`MAX_PAGE_SIZE`で切り詰める。
+    pub fn all_kitties_paged(start: u64, limit: u64) -> Vec<(T::Hash, Kitty<T::Hash, T::Balance>)> {
+        if !T::ENUMERATE_ALL_KITTIES {
+            return Vec::new();
+        }
+
+        let end = start.saturating_add(cmp::min(limit, MAX_PAGE_SIZE));
+        let end = cmp::min(end, Self::all_kitties_count());
+
+        (start..end)
+            .map(|i| {
+                let kitty_id = Self::kitty_by_index(i);
+                (kitty_id, Self::kitty_with_price(kitty_id))
+            })
+            .collect()
+    }
+
+    // kitty本体と所有者、全体および所有者ごとのインデックスをまとめて返す。存在しないkittyには`None`を返す。
+    pub fn get_kitty(kitty_id: T::Hash) -> Option<KittyInfo<T::AccountId, T::Hash, T::Balance>> {
+        let owner = match Self::kitty_status(kitty_id) {
+            KittyStatus::Owned(owner) => owner,
+            _ => return None,
+        };
+
+        Some(KittyInfo {
+            kitty: Self::kitty_with_price(kitty_id),
+            owner_index: Self::_owner_index_of(&owner, kitty_id),
+            owner,
+            global_index: <AllKittiesIndex<T>>::get(kitty_id),
+        })
+    }
+
+    // 存在するkittyのDNAの先頭3バイトを(R, G, B)として返す。描画用の色で、ゲノム全体を解読しなくても決まる。
+    // 存在しないkittyには`None`を返す。
+    pub fn color_of(kitty_id: T::Hash) -> Option<(u8, u8, u8)> {
+        let dna = Self::dna_bytes(kitty_id)?;
+        if dna.len() < 3 {
+            return None;
+        }
+
+        Some((dna[0], dna[1], dna[2]))
+    }
+
+    // 存在するkittyの総数と`who`が所有するkittyの数を1回の呼び出しで返す。(global count, owned count)
+    pub fn counts_for(who: &T::AccountId) -> (u64, u64) {
+        (Self::all_kitties_count(), Self::owned_kitty_count(who))
+    }
+
+    // 存在するkittyのDNAを生のバイト列で返す。RPCでそのまま16進数にできる。存在しないkittyには`None`を返す。
+    pub fn dna_bytes(kitty_id: T::Hash) -> Option<Vec<u8>> {
+        if !<Kitties<T>>::exists(kitty_id) {
+            return None;
+        }
+
+        Some(Self::kitty(kitty_id).dna.as_ref().to_vec())
+    }
+
+    // kitty本体と所有者、売りに出されているかをまとめて返す。存在しないkittyには`None`を返す。
+    pub fn profile_of(kitty_id: T::Hash) -> Option<KittyProfile<T::AccountId, T::Hash, T::Balance>> {
+        if !<Kitties<T>>::exists(kitty_id) {
+            return None;
+        }
+
+        let kitty = Self::kitty_with_price(kitty_id);
+        let for_sale = !kitty.price.is_zero() && !Self::_listing_expired(kitty_id);
+        Some(KittyProfile {
+            kitty,
+            owner: Self::owner_of(kitty_id),
+            for_sale,
+        })
+    }
+
+    // `who`が今`kitty_id`を買えるかを返す。フロントエンドが購入ボタンを出し分けるためのビューで、状態は変えない。
+    // kittyが存在して期限内の出品中であり、`who`の所有でなく、`who`の自由残高で代金を払えるときだけtrueになる。
+    pub fn can_buy(who: &T::AccountId, kitty_id: T::Hash) -> bool {
+        let owner = match Self::_existing_owner_of(kitty_id) {
+            Ok(owner) => owner,
+            Err(_) => return false,
+        };
+        let price = Self::price_of(kitty_id);
+
+        owner != *who
+            && !price.is_zero()
+            && !Self::_listing_expired(kitty_id)
+            && Self::market_enabled()
+            && !<Leases<T>>::exists(kitty_id)
+            && Self::kitty_lock(kitty_id).is_none()
+            && <balances::Module<T>>::free_balance(who) >= price
+    }
+
+    // `account`が所有するkittyの`start`番目から最大`limit`匹を、ハッシュ値とkittyの組で返す。
+    // `limit`は`MAX_PAGE_SIZE`で切り詰め、範囲外の`start`には空のリストを返す。
+    pub fn owned_kitties(account: T::AccountId, start: u64, limit: u32) -> Vec<(T::Hash, Kitty<T::Hash, T::Balance>)> {
+        if start >= Self::owned_kitty_count(&account) {
+            return Vec::new();
+        }
+
+        Self::_owned_kitty_ids(&account, start, cmp::min(limit as u64, MAX_PAGE_SIZE))
+            .into_iter()
+            .map(|kitty_id| (kitty_id, Self::kitty_with_price(kitty_id)))
+            .collect()
+    }
+
+    // 少なくとも一方の親が同じkittyを、重複なく自分自身を除いて返す。
+    // 親ごとの子供の数に上限があるので、結果も高々`2 * MAX_CHILDREN_PER_KITTY`匹である。
+    pub fn siblings_of(kitty_id: T::Hash) -> Vec<T::Hash> {
+        let (parent_1, parent_2) = match Self::parents_of(kitty_id) {
+            Some(parents) => parents,
+            None => return Vec::new(),
+        };
+
+        let mut siblings = Vec::new();
+        for child in Self::children_of(parent_1).into_iter().chain(Self::children_of(parent_2).into_iter()) {
+            if child != kitty_id && !siblings.contains(&child) {
+                siblings.push(child);
+            }
+        }
+
+        siblings
+    }
+
+    // 2匹を交配させたときの相性を0から100で返す。4つの観点でそれぞれ25点ずつ付ける。
+    // 血縁（同じkitty・親子・兄弟でない）、世代差（`MinGenGap`と`MaxGenGap`の範囲内）、
+    // クールダウン（今交配できるか）、DNAの多様さ（異なるバイトの割合）。どちらかが存在しなければ0を返す。
+    pub fn compatibility(a: T::Hash, b: T::Hash) -> u32 {
+        if !<Kitties<T>>::exists(a) || !<Kitties<T>>::exists(b) {
+            return 0;
+        }
+        let kitty_a = Self::kitty(a);
+        let kitty_b = Self::kitty(b);
+        let mut score = 0;
+
+        if !Self::_are_related(a, b) {
+            score += 25;
+        }
+
+        let gen_gap = cmp::max(kitty_a.gen, kitty_b.gen) - cmp::min(kitty_a.gen, kitty_b.gen);
+        if gen_gap >= Self::min_gen_gap() && Self::max_gen_gap().map_or(true, |max_gap| gen_gap <= max_gap) {
+            score += 25;
+        }
+
+        let now = <system::Module<T>>::block_number();
+        let ready = [a, b].iter().filter(|kitty_id| now >= Self::breeding_cooldown_until(**kitty_id)).count() as u32;
+        score += ready * 25 / 2;
+
+        let genome_a = Self::genome_of(a);
+        let genome_b = Self::genome_of(b);
+        let total_bytes = cmp::max(genome_a.len(), genome_b.len()) as u32;
+        if total_bytes > 0 {
+            let same_bytes = genome_a.iter().zip(genome_b.iter()).filter(|(x, y)| x == y).count() as u32;
+            score += (total_bytes - same_bytes) * 25 / total_bytes;
+        }
+
+        score
+    }
+
+    // これまでに現れたDNAの種類の数と、現在のkittyの数を返す。
+    pub fn dna_uniqueness() -> (u64, u64) {
+        (Self::dna_pattern_count(), Self::all_kitties_count())
+    }
+
+    // 初めて現れたDNAなら種類の数を1つ増やす。
+    fn _record_dna(dna: T::Hash) {
+        if !<SeenDna<T>>::get(dna) {
+            <SeenDna<T>>::insert(dna, true);
+            <DnaPatternCount<T>>::mutate(|count| *count = count.saturating_add(1));
+        }
+    }
+
+    // kittyの遺伝子を返す。遺伝子が導入される前のkittyは`dna`から導く。
+    pub fn genome_of(kitty_id: T::Hash) -> Vec<u8> {
+        if <Genomes<T>>::exists(kitty_id) {
+            <Genomes<T>>::get(kitty_id)
+        } else {
+            Self::_expand_genome(Self::kitty(kitty_id).dna)
+        }
+    }
+
+    // `seed`から`GENOME_LENGTH`バイトの遺伝子を作る。`seed`そのものから始め、足りなければ`seed`と番号のハッシュをつなげる。
+    fn _expand_genome(seed: T::Hash) -> Vec<u8> {
+        let length = T::GENOME_LENGTH as usize;
+        let mut genome: Vec<u8> = seed.as_ref().iter().take(length).cloned().collect();
+        let mut round: u32 = 0;
+        while genome.len() < length {
+            let chunk = (seed, round).using_encoded(<T as system::Trait>::Hashing::hash);
+            let remaining = length - genome.len();
+            genome.extend(chunk.as_ref().iter().take(remaining));
+            round += 1;
+        }
+        genome
+    }
+
+    // 同じkitty、親子、または親を共有する兄弟ならtrueを返す。
+    fn _are_related(a: T::Hash, b: T::Hash) -> bool {
+        if a == b {
+            return true;
+        }
+        let parents_a = Self::parents_of(a);
+        let parents_b = Self::parents_of(b);
+        if parents_a.map_or(false, |(p1, p2)| p1 == b || p2 == b) || parents_b.map_or(false, |(p1, p2)| p1 == a || p2 == a) {
+            return true;
+        }
+        match (parents_a, parents_b) {
+            (Some((a1, a2)), Some((b1, b2))) => a1 == b1 || a1 == b2 || a2 == b1 || a2 == b2,
+            _ => false,
+        }
+    }
+
+    // 今交配した場合に親に課されるクールダウンのブロック数を返す。
+    pub fn current_breeding_cooldown() -> T::BlockNumber {
+        let base = Self::base_breeding_cooldown();
+        let threshold = Self::congestion_threshold();
+        if threshold == 0 {
+            return base;
+        }
+
+        // 掛けた結果が溢れるほど混雑しているときは、表せる最大のクールダウンにする。
+        let multiplier = 1 + (Self::recent_breedings() / threshold) as u64;
+        base.checked_mul(&<T::BlockNumber as As<u64>>::sa(multiplier))
+            .unwrap_or_else(|| <T::BlockNumber as As<u64>>::sa(u64::max_value()))
+    }
+
+    // そのハッシュ値のkittyが過去に一度でもmintされたかを返す。
+    // 削除されたkittyと一度もmintされていないハッシュ値を区別するために使う。
+    pub fn ever_existed(kitty_id: T::Hash) -> bool {
+        <EverExisted<T>>::get(kitty_id)
+    }
+
+    // kittyの存在と所有者の有無を一度に調べる。
+    pub fn kitty_status(kitty_id: T::Hash) -> KittyStatus<T::AccountId> {
+        if !<Kitties<T>>::exists(kitty_id) {
+            return KittyStatus::Nonexistent;
+        }
+
+        match Self::owner_of(kitty_id) {
+            Some(owner) => KittyStatus::Owned(owner),
+            None => KittyStatus::Ownerless,
+        }
+    }
+
+    // 挑戦者と挑まれた側のDNAと乱数`seed`から対戦の得点を求める。(challenger score, defender score)
+    // バイトごとに1回戦とし、そのバイトをseedの同じ位置のバイトとXORした値が大きい側が1点を得る。同じ値なら引き分けで得点はない。
+    // 長さが違うときは短い方に合わせる。得点が同じなら引き分けで、双方に賭け金を返す。
+    // 状態を読まない純粋な関数なので、フロントエンドはseedを変えて勝率を見積もれる。
+    pub fn battle_scores(challenger_dna: &[u8], defender_dna: &[u8], seed: &[u8]) -> (u32, u32) {
+        let mut scores = (0, 0);
+        for ((c, d), r) in challenger_dna.iter().zip(defender_dna.iter()).zip(seed.iter()) {
+            let (c, d) = (c ^ r, d ^ r);
+            if c > d {
+                scores.0 += 1;
+            } else if d > c {
+                scores.1 += 1;
+            }
+        }
+        scores
+    }
+
+    // 受けられた対戦の勝敗をこのブロックの乱数で決め、確保してある双方の賭け金を精算する。
+    // 勝った側は相手の賭け金を受け取り、自分の賭け金の確保を外す。引き分けなら双方の確保を外すだけ。
+    fn _resolve_battle(challenger_kitty: T::Hash) {
+        let (challenge, defender_owner) = match (Self::pending_challenge(challenger_kitty), Self::accepted_challenge(challenger_kitty)) {
+            (Some(challenge), Some(defender_owner)) => (challenge, defender_owner),
+            _ => return,
+        };
+
+        let seed = (T::Randomness::random(b"battle"), challenger_kitty, challenge.defender)
+            .using_encoded(<T as system::Trait>::Hashing::hash);
+        let (challenger_score, defender_score) = Self::battle_scores(
+            Self::kitty(challenger_kitty).dna.as_ref(),
+            Self::kitty(challenge.defender).dna.as_ref(),
+            seed.as_ref(),
+        );
+
+        // 双方とも賭け金を確保しているので、受け取る側の口座は消えておらず、移し替えは失敗しない。
+        let winner = if challenger_score > defender_score {
+            let _ = <balances::Module<T>>::repatriate_reserved(&defender_owner, &challenge.challenger, challenge.wager);
+            <balances::Module<T>>::unreserve(&challenge.challenger, challenge.wager);
+            Some(challenge.challenger)
+        } else if defender_score > challenger_score {
+            let _ = <balances::Module<T>>::repatriate_reserved(&challenge.challenger, &defender_owner, challenge.wager);
+            <balances::Module<T>>::unreserve(&defender_owner, challenge.wager);
+            Some(defender_owner)
+        } else {
+            <balances::Module<T>>::unreserve(&challenge.challenger, challenge.wager);
+            <balances::Module<T>>::unreserve(&defender_owner, challenge.wager);
+            None
+        };
+        <Challenges<T>>::remove(challenger_kitty);
+        <AcceptedChallenges<T>>::remove(challenger_kitty);
+
+        Self::deposit_event(RawEvent::BattleResolved(challenger_kitty, challenge.defender, challenger_score, defender_score, winner, challenge.wager));
+    }
+
+    // ダッチオークションの`now`時点での価格を線形補間で求める。終了ブロック以降は最終価格のまま。
+    pub fn dutch_price(auction: &DutchAuction<T::AccountId, T::Balance, T::BlockNumber>, now: T::BlockNumber) -> T::Balance {
+        if now >= auction.end_block {
+            return auction.end_price;
+        }
+
+        let elapsed = <T::Balance as As<u64>>::sa(now.saturating_sub(auction.start_block).as_());
+        let duration = <T::Balance as As<u64>>::sa((auction.end_block - auction.start_block).as_());
+        let drop = (auction.start_price - auction.end_price) * elapsed / duration;
+
+        auction.start_price - drop
+    }
+
+    // `MigrationCursor`から最大`limit`匹のkittyを移行する。途中で止まっても次の呼び出しで続きから再開する。
+    // すべてのkittyを移行し終えたら`StorageVersion`を上げる。
+    fn _migrate_storage(limit: u64) {
+        let cursor = Self::migration_cursor();
+        let count = Self::all_kitties_count();
+        let end = cmp::min(cursor.saturating_add(limit), count);
+
+        for i in cursor..end {
+            Self::_migrate_kitty(Self::kitty_by_index(i));
+        }
+
+        if end >= count {
+            <StorageVersion<T>>::put(CURRENT_STORAGE_VERSION);
+            <MigrationCursor<T>>::kill();
+            Self::deposit_event(RawEvent::StorageMigrated(CURRENT_STORAGE_VERSION));
+        } else {
+            <MigrationCursor<T>>::put(end);
+        }
+    }
+
+    // 移行中なら、これから触るkittyを先に移行しておく。
+    fn _ensure_migrated(kitty_id: T::Hash) {
+        if Self::storage_version() < CURRENT_STORAGE_VERSION {
+            Self::_migrate_kitty(kitty_id);
+        }
+    }
+
+    // 1匹のkittyを現在のレイアウトに移行する。
+    // バージョン3：所有者ごとのリストをインデックスの配列（バージョン1のタプルをキーとするmap、
+    // バージョン2のdouble map）から`OwnedKittiesList`の連結リストへ移す。
+    // 移行前に転送されてすでにリストに入っているkittyは追加しない。
+    // バージョン4：既存のkittyのDNAを`SeenDna`と`DnaPatternCount`に数える。
+    // バージョン5：`Kitty`の`price`を`Listings`へ移し、`price`を0にする。移行前に出品し直したkittyは`Listings`を優先する。
+    // バージョン6：既存のkittyを`KittiesPerGen`と`HighestGen`に数える。
+    // バージョン7：売りに出されているkittyを`ForSaleArray`に載せる。
+    // バージョン8：既存のkittyを`KittiesByGeneration`に載せる。世代の一覧がいっぱいなら載せない。
+    // バージョン9：作者の記録がないkittyは、移行時の所有者を作者として`KittyCreator`に記録する。
+    fn _migrate_kitty(kitty_id: T::Hash) {
+        if <Kitties<T>>::exists(kitty_id) {
+            let mut kitty = Self::kitty(kitty_id);
+            Self::_record_dna(kitty.dna);
+
+            if !<GenCounted<T>>::get(kitty_id) {
+                <GenCounted<T>>::insert(kitty_id, true);
+                <KittiesPerGen<T>>::mutate(kitty.gen, |count| *count = count.saturating_add(1));
+                if kitty.gen > Self::highest_gen() {
+                    <HighestGen<T>>::put(kitty.gen);
+                }
+            }
+
+            if !kitty.price.is_zero() {
+                if !<Listings<T>>::exists(kitty_id) {
+                    <Listings<T>>::insert(kitty_id, kitty.price);
+                }
+                kitty.price = Zero::zero();
+                <Kitties<T>>::insert(kitty_id, &kitty);
+            }
+
+            if <Listings<T>>::exists(kitty_id) {
+                Self::_add_to_for_sale(kitty_id);
+            }
+
+            <KittiesByGeneration<T>>::mutate(kitty.gen, |kitties| {
+                if !kitties.contains(&kitty_id) && kitties.len() < MAX_KITTIES_PER_GENERATION {
+                    kitties.push(kitty_id);
+                }
+            });
+        }
+
+        if let Some(owner) = Self::owner_of(kitty_id) {
+            if <OwnedKittiesIndex<T>>::exists(kitty_id) {
+                let index = <OwnedKittiesIndex<T>>::take(kitty_id);
+                <OwnedKitties<T>>::remove(&owner, index);
+                <OwnedKittiesArray<T>>::remove((owner.clone(), index));
+            }
+            if !<OwnedKittiesList<T>>::exists((owner.clone(), Some(kitty_id))) {
+                Self::_append_owned_kitty(&owner, kitty_id);
+            }
+            if !<KittyCreator<T>>::exists(kitty_id) {
+                <KittyCreator<T>>::insert(kitty_id, &owner);
+            }
+        }
+    }
+
+    // 世代`gen`に`count`匹のkittyが新たに生まれても`MAX_KITTIES_PER_GENERATION`を超えないことを確認する。
+    fn _ensure_generation_has_room(gen: u64, count: u32) -> Result {
+        let new_len = Self::kitties_in_generation(gen).len().saturating_add(count as usize);
+        ensure!(new_len <= MAX_KITTIES_PER_GENERATION, Error::GenerationFull.as_str());
+        Ok(())
+    }
+
+    // 所有するkittyが`new_count`匹になっても`MaxKittiesOwned`を超えないことを確認する。
+    fn _ensure_can_own(new_count: u64) -> Result {
+        let max_owned = Self::max_kitties_owned();
+        ensure!(max_owned == 0 || new_count <= max_owned, Error::TooManyKittiesOwned.as_str());
+        Ok(())
+    }
+
+    // `who`が`amount`を支払った後も、残高が存在保証金を下回らないことを確認する。
+    fn _ensure_remains_above_existential_deposit(who: &T::AccountId, amount: T::Balance) -> Result {
+        let free_balance = <balances::Module<T>>::free_balance(who);
+        let remaining = free_balance.checked_sub(&amount)
+            .ok_or(Error::InsufficientBalance)?;
+        ensure!(remaining >= <balances::Module<T>>::existential_deposit(), "Error: paying the fee would drop your balance below the existential deposit");
+
+        Ok(())
+    }
+
+    // 現在のウィンドウでの呼び出し回数を数え、上限を超えていたら拒否する。
+    // ウィンドウは最初の呼び出しから`RateWindow`ブロックで切り替わる。
+    fn _check_rate_limit(who: &T::AccountId) -> Result {
+        let max_calls = Self::max_calls_per_window();
+        if max_calls == 0 {
+            return Ok(());
+        }
+
+        let now = <system::Module<T>>::block_number();
+        let (calls, window_start) = Self::rate_limit(who);
+        let window_end = window_start.checked_add(&Self::rate_window()).ok_or(Error::Overflow)?;
+        let (calls, window_start) = if calls == 0 || now >= window_end {
+            (0, now)
+        } else {
+            (calls, window_start)
+        };
+        ensure!(calls < max_calls, "Error: too many calls in the current rate limit window");
+
+        <RateLimit<T>>::insert(who, (calls + 1, window_start));
+
+        Ok(())
+    }
+
+    // オークションとその設定を片付け、kittyのロックを外す。入札の記録は保存期間が過ぎてから消す。
+    fn _close_auction(kitty_id: T::Hash, seller: &T::AccountId) {
+        <Auctions<T>>::remove(kitty_id);
+        <AuctionExtensions<T>>::remove(kitty_id);
+        <AuctionReserves<T>>::remove(kitty_id);
+        <AuctionBuyNow<T>>::remove(kitty_id);
+        if <BidHistory<T>>::exists(kitty_id) {
+            // 最後のブロックを超える期限は最後のブロックに丸める。
+            let expires_at = <system::Module<T>>::block_number().saturating_add(Self::bid_history_retention());
+            <BidHistoriesExpiringAt<T>>::mutate(expires_at, |kitties| kitties.push(kitty_id));
+        }
+        Self::unlock_kitty(kitty_id);
+        <ActiveAuctions<T>>::mutate(seller, |count| *count = count.saturating_sub(1));
+    }
+
+    // オークションを精算する。最高入札者が確保していた入札額を出品者へ移してkittyを渡す。
+    // 入札がない・出品者がもう所有者でない場合は落札されずに終了し、確保していた入札額を返す。
+    // 手動と自動のどちらの精算も同じイベントを吐く。
+    fn _settle_auction(kitty_id: T::Hash, auction: Auction<T::AccountId, T::Balance, T::BlockNumber>) {
+        let reserve = Self::auction_reserve(kitty_id);
+        Self::_close_auction(kitty_id, &auction.seller);
+
+        if let Some(winner) = auction.highest_bidder {
+            // 最高入札額が最低落札価格に届かなければ売らずに終える。入札額は返し、kittyは出品者の手元に残る。
+            if let Some((reserve_price, _)) = reserve {
+                if auction.highest_bid < reserve_price {
+                    <balances::Module<T>>::unreserve(&winner, auction.highest_bid);
+                    Self::deposit_event(RawEvent::AuctionFailed(auction.seller, kitty_id, auction.highest_bid, reserve_price));
+                    return;
+                }
+            }
+
+            // 手数料やロイヤリティを払えない、kittyを渡せないなどで売れなければ、落札されずに終える。
+            if Self::_sell_at_auction(kitty_id, &auction.seller, &winner, auction.highest_bid).is_ok() {
+                Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+                Self::_record_sale(kitty_id, &winner, &auction.seller, auction.highest_bid);
+                Self::deposit_event(RawEvent::AuctionSettled(auction.seller, winner, kitty_id, auction.highest_bid));
+                return;
+            }
+
+            <balances::Module<T>>::unreserve(&winner, auction.highest_bid);
+        }
+
+        Self::deposit_event(RawEvent::AuctionCancelled(auction.seller, kitty_id));
+    }
+
+    // 落札者`winner`が確保していた`price`で、`seller`から`kitty_id`を売る。
+    // 代金の分け方とkittyを渡せることを先にすべて確認し、どれかが満たせなければ何も動かさずにエラーを返す。
+    fn _sell_at_auction(kitty_id: T::Hash, seller: &T::AccountId, winner: &T::AccountId, price: T::Balance) -> Result {
+        Self::_ensure_not_leased(kitty_id)?;
+        Self::_ensure_can_transfer(seller, winner, kitty_id)?;
+        let split = Self::_sale_split(kitty_id, seller, price)?;
+        Self::_ensure_can_pay_sale_from_reserved(winner, seller, price, &split)?;
+
+        Self::_pay_sale_from_reserved(winner, seller, kitty_id, split)?;
+        Self::_transfer_from(seller.clone(), winner.clone(), kitty_id)?;
+
+        Ok(())
+    }
+
+    // `sender`に第0世代のkittyを`count`匹生成する。生成数の制限やクールダウンは一度の呼び出しにつき1回確認する。
+    fn _create_kitties(sender: T::AccountId, count: u32) -> Result {
+
+        // Verify first, write lastの原則：キュレーションされたコレクションでは許可されたアカウントだけが生成できる。
+        ensure!(!Self::restrict_minting() || Self::is_minter(&sender), Error::NotMinter.as_str());
+
+        // Verify first, write lastの原則：このブロックでの生成数が上限を超えないことを確認する。
+        let now = <system::Module<T>>::block_number();
+        let (last_block, last_count) = Self::mints_in_block();
+        let mints_in_block = if last_block == now { last_count } else { 0 };
+        let new_mints_in_block = mints_in_block.checked_add(count).ok_or(Error::Overflow)?;
+        let max_mints = Self::max_mints_per_block();
+        ensure!(max_mints == 0 || new_mints_in_block <= max_mints, Error::BlockMintLimitReached.as_str());
+
+        // Verify first, write lastの原則：nonceを使う前に、このアカウントのクールダウンが明けていることを確認する。
+        if let Some(last_create_block) = Self::last_create_block(&sender) {
+            let cooldown_end = last_create_block.checked_add(&Self::create_cooldown()).ok_or(Error::Overflow)?;
+            ensure!(now >= cooldown_end, Error::CreateCooldown.as_str());
+        }
+
+        // Verify first, write lastの原則：全匹分の手数料を払い、預り金を確保しても残高が存在保証金を下回らないことを確認する。
+        let deposit = Self::kitty_deposit();
+        let count_balance = <T::Balance as As<u64>>::sa(count as u64);
+        let total_deposit = deposit.checked_mul(&count_balance).ok_or(Error::Overflow)?;
+        let total_fee = Self::creation_fee().checked_mul(&count_balance).ok_or(Error::Overflow)?;
+        let treasury = if total_fee.is_zero() {
+            None
+        } else {
+            let treasury = Self::treasury().ok_or(Error::NoTreasury)?;
+            Self::_ensure_can_receive(&treasury, total_fee)?;
+            Some(treasury)
+        };
+        Self::_ensure_remains_above_existential_deposit(&sender, total_deposit.checked_add(&total_fee).ok_or(Error::Overflow)?)?;
+
+        // Verify first, write lastの原則：途中で失敗しないよう、すべて生成しても個体数とnonceがoverflowせず、所有できる数を超えないことを確認する。
+        let new_owned_kitty_count = Self::owned_kitty_count(&sender).checked_add(count as u64).ok_or(Error::Overflow)?;
+        Self::_ensure_can_own(new_owned_kitty_count)?;
+        Self::_ensure_generation_has_room(0, count)?;
+        Self::all_kitties_count().checked_add(count as u64).ok_or(Error::Overflow)?;
+
+        // Verify first, write lastの原則：第0世代のkittyの総数が上限を超えないことを確認する。
+        let new_gen0_count = Self::gen0_count().checked_add(count as u64).ok_or(Error::Overflow)?;
+        ensure!(T::GEN0_CAP == 0 || new_gen0_count <= T::GEN0_CAP, Error::Gen0CapReached.as_str());
+
+        let nonce = <Nonce<T>>::get();
+        nonce.checked_add(count as u64).ok_or(Error::Overflow)?;
+
+        // senderからnonceと合わせてハッシュ値を計算する。`_mint`がnonceを1つずつ進めるので、i匹目はnonce + iを使う。
+        // 「random_hash <--> kitty」は一対一対応している。
+        let mut kitty_ids = Vec::with_capacity(count as usize);
+        for index in 0..count as u64 {
+            let random_hash = (T::Randomness::random(b"create_kitty"), &sender, nonce + index)
+                .using_encoded(<T as system::Trait>::Hashing::hash);
+
+            // 計算したrandom_hashが衝突していないことを確認する。
+            ensure!(!<KittyOwner<T>>::exists(random_hash) && !kitty_ids.contains(&random_hash), Error::KittyAlreadyExists.as_str());
+            kitty_ids.push(random_hash);
+        }
+
+        // 手数料をトレジャリーへ払い、全匹分の預り金をまとめて確保する。どちらも先に確認してあるので、kittyを生成する前に行う。
+        if let Some(treasury) = treasury {
+            <balances::Module<T>>::make_transfer(&sender, &treasury, total_fee)?;
+        }
+        if !total_deposit.is_zero() {
+            <balances::Module<T>>::reserve(&sender, total_deposit)?;
+        }
+
+        let royalty = Self::creator_royalty(&sender);
+        for random_hash in kitty_ids {
+            // new_kittyを生成する。
+            let new_kitty = Kitty {
+                id: random_hash,
+                dna: random_hash,
+                price: <T::Balance as As<u64>>::sa(0),
+                gen: 0,
+            };
+
+            // 新たに生成されたkittyを記録する。
+            let genome = Self::_expand_genome(random_hash);
+            Self::_mint(sender.clone(), random_hash, new_kitty, genome, KittyOrigin::Minted)?;
+
+            // 転売のたびに作者がロイヤリティを受け取れるよう、生成時のロイヤリティ率を記録する。
+            if royalty > 0 {
+                <KittyRoyalty<T>>::insert(random_hash, royalty);
+            }
+
+            // 確保した預り金をkittyごとに記録する。
+            if !deposit.is_zero() {
+                <KittyDeposits<T>>::insert(random_hash, deposit);
+            }
+        }
+
+        // このブロックでの生成数を数える。ブロックが変わったら数え直す。
+        <MintsInBlock<T>>::put((now, new_mints_in_block));
+        <LastCreateBlock<T>>::insert(&sender, now);
+        <Gen0Count<T>>::put(new_gen0_count);
+
+        Ok(())
+    }
+
+    // `payer`が売りに出されたkittyを代金を払って購入し、`recipient`へ転送する。売り手と代金を返す。
+    fn _buy_kitty(payer: &T::AccountId, recipient: T::AccountId, kitty_id: T::Hash, max_price: T::Balance) -> result::Result<(T::AccountId, T::Balance), &'static str> {
+
+        // Verify first, write lastの原則：買いたいkittyが存在し、所有者が正当であることを確認する。
+        let owner = Self::_existing_owner_of(kitty_id)?;
+        ensure!(owner != *payer && owner != recipient, Error::BuyOwnKitty.as_str());
+        Self::_ensure_not_leased(kitty_id)?;
+        Self::_ensure_unlocked(kitty_id)?;
+
+        // 売却額を確認する。
+        let kitty_price = Self::price_of(kitty_id);
+
+        // 売却額 == 0のkittyは売却対象ではないものとする。
+        ensure!(!kitty_price.is_zero(), Error::NotForSale.as_str());
+
+        // 期限が過ぎた出品は売りに出されていないものとして扱う。
+        ensure!(!Self::_listing_expired(kitty_id), Error::ListingExpired.as_str());
+
+        // 買取側の口座残高が売却額以下でないと買えないので確認する。
+        ensure!(kitty_price <= max_price, Error::PriceTooHigh.as_str());
+
+        // Verify first, write lastの原則：売り手の累計販売額に応じた手数料と作者へのロイヤリティを確認する。
+        let split = Self::_sale_split(kitty_id, &owner, kitty_price)?;
+
+        // Verify first, write lastの原則：代金を動かした後にkittyの転送が失敗しないよう、先に転送できることを確認する。
+        Self::_ensure_can_transfer(&owner, &recipient, kitty_id)?;
+
+        // Verify first, write lastの原則：代金を分けて送るときは、1回目の後で残りが失敗しないよう残高を確認しておく。
+        Self::_ensure_can_pay_sale(payer, <balances::Module<T>>::free_balance(payer), &owner, kitty_price, &split)?;
+
+        // 双方の残高をアトミックに更新する。手数料とロイヤリティは代金から差し引いてトレジャリーと作者へ送る。
+        Self::_pay_sale(payer, &owner, kitty_id, split)?;
+
+        // kittyを売却側から受け取る側へ転送する。
+        Self::_transfer_from(owner.clone(), recipient, kitty_id)?;
+
+        // 売れたので出品を取り下げる。
+        Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+        Self::_record_sale(kitty_id, payer, &owner, kitty_price);
+
+        Ok((owner, kitty_price))
+    }
+
+    // `owner`のkittyを削除する。一度存在したことは`EverExisted`に残る。
+    fn _burn(owner: T::AccountId, kitty_id: T::Hash) -> Result {
+
+        // Verify first, write lastの原則：貸し出し中やオークション中、ロック中のkittyは削除できない。
+        Self::_ensure_not_leased(kitty_id)?;
+        ensure!(!<Auctions<T>>::exists(kitty_id), "Error: this kitty is on an auction");
+        ensure!(!<DutchAuctions<T>>::exists(kitty_id), "Error: this kitty is on a dutch auction");
+        Self::_ensure_unlocked(kitty_id)?;
+
+        // Verify first, write lastの原則：この世代の数から引けることを確認する。移行中でまだ数えていないkittyは引かない。
+        let gen = Self::kitty(kitty_id).gen;
+        let counted = Self::storage_version() >= CURRENT_STORAGE_VERSION || <GenCounted<T>>::get(kitty_id);
+        let new_kitties_in_gen = if counted {
+            Some(Self::kitties_in_gen(gen).checked_sub(1).ok_or(Error::Underflow)?)
+        } else {
+            None
+        };
+
+        // 出品を取り下げ、所有者ごとと全体のリストから取り除く。
+        Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+        Self::_remove_from_owner(&owner, kitty_id)?;
+        Self::_remove_from_all(kitty_id)?;
+
+        // 確保されていた預り金を今の所有者に戻す。
+        let deposit = <KittyDeposits<T>>::take(kitty_id);
+        if !deposit.is_zero() {
+            <balances::Module<T>>::unreserve(&owner, deposit);
+        }
+
+        // kitty本体と付随する設定を削除する。親子関係と来歴は系譜のために残す。
+        <Kitties<T>>::remove(kitty_id);
+        <KittyOwner<T>>::remove(kitty_id);
+        <Genomes<T>>::remove(kitty_id);
+        <KittyUri<T>>::remove(kitty_id);
+        <Watchers<T>>::remove(kitty_id);
+        <KittyRoyalty<T>>::remove(kitty_id);
+        <StudFee<T>>::remove(kitty_id);
+        <Sterile<T>>::remove(kitty_id);
+        <PriceFloor<T>>::remove(kitty_id);
+        <BreedingCooldownUntil<T>>::remove(kitty_id);
+        <LeaseOffers<T>>::remove(kitty_id);
+        <Adoptable<T>>::remove(kitty_id);
+        <GenCounted<T>>::remove(kitty_id);
+
+        // 世代ごとの数と一覧から取り除く。最も新しい世代は、その世代が絶えても記録として残す。
+        if let Some(new_kitties_in_gen) = new_kitties_in_gen {
+            <KittiesPerGen<T>>::insert(gen, new_kitties_in_gen);
+        }
+        <KittiesByGeneration<T>>::mutate(gen, |kitties| kitties.retain(|id| *id != kitty_id));
+
+        T::OnKittyTransfer::on_burn(&owner, &kitty_id);
+
+        Self::deposit_event(RawEvent::Burned(owner, kitty_id));
+
+        Ok(())
+    }
+
+    // `sender`が`kitty_id_1`と`kitty_id_2`を親として`litter_size`匹の子供を生まれさせる。
+    fn _breed(sender: T::AccountId, kitty_id_1: T::Hash, kitty_id_2: T::Hash, litter_size: u32) -> Result {
+
+        // Verify first, write lastの原則：kittyの存在確認。
+        ensure!(<Kitties<T>>::exists(kitty_id_1), Error::KittyNotFound.as_str());
+        ensure!(<Kitties<T>>::exists(kitty_id_2), Error::KittyNotFound.as_str());
+
+        // Verify first, write lastの原則：交配できないようにされたkittyは親にできない。
+        ensure!(!Self::is_sterile(kitty_id_1), Error::Sterile.as_str());
+        ensure!(!Self::is_sterile(kitty_id_2), Error::Sterile.as_str());
+
+        // Verify first, write lastの原則：ステーク中のkittyは親にできない。
+        ensure!(!<StakedKitties<T>>::exists(kitty_id_1), Error::KittyStaked.as_str());
+        ensure!(!<StakedKitties<T>>::exists(kitty_id_2), Error::KittyStaked.as_str());
+
+        // Verify first, write lastの原則：他人のkittyは種付け料が設定されているときだけ親にできる。
+        let mut stud_fees = Vec::new();
+        let mut total_stud_fee = <T::Balance as As<u64>>::sa(0);
+        for parent_id in [kitty_id_1, kitty_id_2].iter() {
+            let parent_owner = Self::_existing_owner_of(*parent_id)?;
+            if parent_owner != sender {
+                let fee = Self::stud_fee(*parent_id);
+                ensure!(!fee.is_zero(), Error::BreedingNotAllowed.as_str());
+                total_stud_fee = total_stud_fee.checked_add(&fee).ok_or(Error::Overflow)?;
+                Self::_ensure_can_receive(&parent_owner, fee)?;
+                stud_fees.push((parent_owner, fee));
+            }
+        }
+
+        // Verify first, write lastの原則：交配手数料と種付け料をまとめて支払えることを確認する。
+        let breed_fee = Self::breed_fee();
+        let total_fee = total_stud_fee.checked_add(&breed_fee).ok_or(Error::Overflow)?;
+        ensure!(<balances::Module<T>>::free_balance(&sender) >= total_fee, Error::InsufficientBalance.as_str());
+        // 支払いを分けて送るときは、途中で失敗しないよう存在保証金を残させる。
+        let payments = stud_fees.len() + if breed_fee.is_zero() { 0 } else { 1 };
+        if payments > 1 {
+            Self::_ensure_remains_above_existential_deposit(&sender, total_fee)?;
+        }
+        let treasury = if breed_fee.is_zero() || Self::burn_breed_fees() {
+            None
+        } else {
+            let treasury = Self::treasury().ok_or(Error::NoTreasury)?;
+            Self::_ensure_can_receive(&treasury, breed_fee)?;
+            Some(treasury)
+        };
+
+        // Verify first, write lastの原則：生まれる子供の数が上限以下であることを確認する。
+        ensure!(litter_size > 0, Error::EmptyLitter.as_str());
+        ensure!(litter_size <= MAX_LITTER_SIZE, Error::LitterTooLarge.as_str());
+
+        // Verify first, write lastの原則：子供をすべて所有しても所有できる数を超えないことを確認する。
+        let new_owned_kitty_count = Self::owned_kitty_count(&sender).checked_add(litter_size as u64).ok_or(Error::Overflow)?;
+        Self::_ensure_can_own(new_owned_kitty_count)?;
+        Self::all_kitties_count().checked_add(litter_size as u64).ok_or(Error::Overflow)?;
+        <Nonce<T>>::get().checked_add(litter_size as u64).ok_or(Error::Overflow)?;
+
+        // Verify first, write lastの原則：どちらの親も、子供が生まれた後に子供の数が上限を超えないことを確認する。
+        ensure!(<Children<T>>::get(kitty_id_1).len() + litter_size as usize <= MAX_CHILDREN_PER_KITTY, Error::TooManyChildren.as_str());
+        ensure!(<Children<T>>::get(kitty_id_2).len() + litter_size as usize <= MAX_CHILDREN_PER_KITTY, Error::TooManyChildren.as_str());
+
+        // Verify first, write lastの原則：どちらの親もクールダウン中でないことを確認する。
+        let now = <system::Module<T>>::block_number();
+        ensure!(now >= Self::breeding_cooldown_until(kitty_id_1), Error::BreedingCooldown.as_str());
+        ensure!(now >= Self::breeding_cooldown_until(kitty_id_2), Error::BreedingCooldown.as_str());
+        let cooldown_until = now.checked_add(&Self::current_breeding_cooldown()).ok_or(Error::Overflow)?;
+
+        // 親を引き出す。
+        let kitty_1 = Self::kitty(kitty_id_1);
+        let kitty_2 = Self::kitty(kitty_id_2);
+
+        // Verify first, write lastの原則：親の世代差が許される範囲にあることを確認する。
+        let gen_gap = cmp::max(kitty_1.gen, kitty_2.gen) - cmp::min(kitty_1.gen, kitty_2.gen);
+        ensure!(gen_gap >= Self::min_gen_gap(), Error::GenGapTooSmall.as_str());
+        if let Some(max_gen_gap) = Self::max_gen_gap() {
+            ensure!(gen_gap <= max_gen_gap, Error::GenGapTooLarge.as_str());
+        }
+
+        // Verify first, write lastの原則：子供の世代にすべての子供が入る空きがあることを確認する。
+        let child_gen = cmp::max(kitty_1.gen, kitty_2.gen).checked_add(1).ok_or(Error::Overflow)?;
+        Self::_ensure_generation_has_room(child_gen, litter_size)?;
+
+        let genome_1 = Self::genome_of(kitty_id_1);
+        let genome_2 = Self::genome_of(kitty_id_2);
+
+        // 子供を1匹ずつ用意する。`_mint`がnonceを1つずつ進めるので、i匹目のidはnonce + iから計算する。
+        let nonce = <Nonce<T>>::get();
+        let mut litter = Vec::with_capacity(litter_size as usize);
+        for index in 0..litter_size as u64 {
+            // 子供に振られるidを計算する。
+            let child_nonce = nonce.checked_add(index).ok_or(Error::Overflow)?;
+            let random_hash = (T::Randomness::random(b"breed_kitty"), &sender, child_nonce)
+                                .using_encoded(<T as system::Trait>::Hashing::hash);
+            ensure!(!<KittyOwner<T>>::exists(random_hash), Error::KittyAlreadyExists.as_str());
+            ensure!(litter.iter().all(|(id, _, _)| *id != random_hash), Error::KittyAlreadyExists.as_str());
+
+            // 最終的な子供のDNA（初期値として片親のDNAをコピー）
+            let mut final_dna = kitty_1.dna;
+
+            // DNAをシェイキング！子供ごとに異なる乱数を使う。
+            for (i, (dna_2_element, r)) in kitty_2.dna.as_ref().iter().zip(random_hash.as_ref().iter()).enumerate() {
+                if r % 2 == 0 {
+                    final_dna.as_mut()[i] = *dna_2_element;
+                }
+            }
+
+            // 親の遺伝子を乱数に従って1バイトずつ受け継ぐ。親の遺伝子が短ければ繰り返して使う。
+            let mut child_genome = Vec::with_capacity(T::GENOME_LENGTH as usize);
+            for (i, r) in Self::_expand_genome(random_hash).into_iter().enumerate() {
+                let genome = if r % 2 == 0 { &genome_2 } else { &genome_1 };
+                child_genome.push(if genome.is_empty() { r } else { genome[i % genome.len()] });
+            }
+
+            // 子供誕生
+            let new_kitty = Kitty {
+                id: random_hash,
+                dna: final_dna,
+                price: <T::Balance as As<u64>>::sa(0),
+                gen: child_gen,
+            };
+            litter.push((random_hash, new_kitty, child_genome));
+        }
+
+        // 交配手数料をトレジャリーに支払うか、焼却して総発行量を減らす。
+        // 焼却は最初に行い、払いきれなければ他の支払いの前に失敗させる。
+        if !breed_fee.is_zero() {
+            match treasury {
+                Some(treasury) => <balances::Module<T>>::make_transfer(&sender, &treasury, breed_fee)?,
+                None => {
+                    let unslashed = <balances::Module<T>>::slash(&sender, breed_fee);
+                    ensure!(unslashed.map_or(true, |unslashed| unslashed.is_zero()), Error::InsufficientBalance.as_str());
+                }
+            }
+        }
+
+        // 他人の親の所有者に種付け料を支払う。
+        for (parent_owner, fee) in stud_fees {
+            <balances::Module<T>>::make_transfer(&sender, &parent_owner, fee)?;
+        }
+
+        for (random_hash, new_kitty, child_genome) in litter {
+            // 子供の所有権を記録する。
+            Self::_mint(sender.clone(), random_hash, new_kitty, child_genome, KittyOrigin::Bred)?;
+
+            // 親子関係を記録する。
+            <Parents<T>>::insert(random_hash, (kitty_id_1, kitty_id_2));
+            <Children<T>>::mutate(kitty_id_1, |children| children.push(random_hash));
+            if kitty_id_2 != kitty_id_1 {
+                <Children<T>>::mutate(kitty_id_2, |children| children.push(random_hash));
+            }
+
+            // Createdに加えて、親の情報を含むBredイベントを子供ごとに吐く。
+            Self::deposit_event(RawEvent::Bred(sender.clone(), random_hash, kitty_id_1, kitty_id_2));
+        }
+
+        // 親にクールダウンを課し、最近の交配数に数える。
+        <BreedingCooldownUntil<T>>::insert(kitty_id_1, cooldown_until);
+        <BreedingCooldownUntil<T>>::insert(kitty_id_2, cooldown_until);
+        <RecentBreedings<T>>::mutate(|n| *n = n.saturating_add(1));
+
+        Ok(())
+    }
+
+    // 消された`who`のkittyを先頭から最大`MAX_IMPOUND_BATCH`匹たどって`pound`へ移し、`adopt_kitty`で引き取れるようにする。
+    // 移せなかったkittyもたどった数に数えるので、一度に読むノードの数は`MAX_IMPOUND_BATCH`を超えない。
+    // ロック中や貸し出し中のkittyは移せないので、リストの末尾へ回して次の呼び出しがその先から始められるようにする。
+    // 預り金の確保分がアカウントとともに消えていれば、預り金の記録を消して預り金なしで移す。
+    // 残ったkittyがなくなれば`Reaped`の記録を消す。
+    fn _impound(who: &T::AccountId, pound: &T::AccountId) {
+        if who == pound {
+            return;
+        }
+
+        let mut visited = 0;
+        let mut moved = 0;
+        let mut cursor = Self::owned_kitties_list((who.clone(), None)).and_then(|head| head.next);
+        while let Some(kitty_id) = cursor {
+            if visited >= MAX_IMPOUND_BATCH {
+                break;
+            }
+            visited += 1;
+
+            // 移したり末尾へ回したりするとリストの順番が変わるので、先に次のkittyを読んでおく。
+            cursor = Self::owned_kitties_list((who.clone(), Some(kitty_id))).and_then(|item| item.next);
+
+            if Self::_ensure_not_leased(kitty_id).is_err() || Self::_ensure_unlocked(kitty_id).is_err() {
+                Self::_unlink_owned_kitty(who, kitty_id);
+                Self::_append_owned_kitty(who, kitty_id);
+                continue;
+            }
+
+            if <balances::Module<T>>::reserved_balance(who) < Self::deposit_of(kitty_id) {
+                <KittyDeposits<T>>::remove(kitty_id);
+            }
+
+            if <Self as NonFungible>::transfer(who.clone(), pound.clone(), kitty_id).is_ok() {
+                <Adoptable<T>>::insert(kitty_id, true);
+                moved += 1;
+            }
+        }
+
+        let remaining = Self::owned_kitty_count(who);
+        if remaining == 0 {
+            <Reaped<T>>::remove(who);
+        }
+
+        Self::deposit_event(RawEvent::KittiesImpounded(who.clone(), pound.clone(), moved, remaining));
+    }
+
+    // `count`匹分の転送手数料を`who`が払えることを確認し、その額を返す。手数料がなければ0を返す。
+    fn _ensure_can_pay_transfer_fee(who: &T::AccountId, count: u64) -> result::Result<T::Balance, &'static str> {
+        let fee = <T::Balance as As<u64>>::sa(T::TRANSFER_FEE)
+            .checked_mul(&<T::Balance as As<u64>>::sa(count))
+            .ok_or(Error::Overflow)?;
+        if fee.is_zero() {
+            return Ok(fee);
+        }
+
+        // kittyを動かした後に手数料の送金が失敗しないよう、トレジャリーが受け取れる額であることも確認する。
+        let treasury = Self::treasury().ok_or(Error::NoTreasury)?;
+        Self::_ensure_can_receive(&treasury, fee)?;
+        Self::_ensure_remains_above_existential_deposit(who, fee)?;
+
+        Ok(fee)
+    }
+
+    // 確認済みの転送手数料を`Treasury`へ送る。
+    fn _pay_transfer_fee(who: &T::AccountId, fee: T::Balance) -> Result {
+        if fee.is_zero() {
+            return Ok(());
+        }
+
+        let treasury = Self::treasury().ok_or(Error::NoTreasury)?;
+        <balances::Module<T>>::make_transfer(who, &treasury, fee)
+    }
+
+    // 売買が無効なら出品・購入・オークションの開始と入札はできない。
+    fn _ensure_market_enabled() -> Result {
+        ensure!(Self::market_enabled(), "Error: the market is disabled");
+        Ok(())
+    }
+
+    // ロックされたkittyは転送・出品・削除できない。エラーにはロックの理由を含める。
+    fn _ensure_unlocked(kitty_id: T::Hash) -> Result {
+        match Self::kitty_lock(kitty_id) {
+            Some(reason) => Err(reason.as_str()),
+            None => Ok(()),
+        }
+    }
+
+    // kittyをロックする。すでにロックされていれば、その理由のエラーを返す。
+    // ロックを掛け外しするのはオークションなどの処理だけで、extrinsicから直接は呼べない。
+    fn lock_kitty(kitty_id: T::Hash, reason: LockReason) -> Result {
+        Self::_ensure_unlocked(kitty_id)?;
+        <KittyLocks<T>>::insert(kitty_id, reason);
+        Ok(())
+    }
+
+    // kittyのロックを外す。ロックを掛けた処理が終わったとき（取り下げ・精算・返却）に呼ぶ。
+    fn unlock_kitty(kitty_id: T::Hash) {
+        <KittyLocks<T>>::remove(kitty_id);
+    }
+
+    // 貸し出し中のkittyは転送・出品できない。
+    fn _ensure_not_leased(kitty_id: T::Hash) -> Result {
+        ensure!(!<Leases<T>>::exists(kitty_id), "Error: this kitty is leased and can not be transferred or listed");
+        Ok(())
+    }
+
+    // 貸し出しを終了してkittyを貸し手へ戻す。期限切れと合意による終了とでイベントを分ける。
+    // 返却できないときは何も書き込まずにエラーを返し、貸し出しはそのまま残る。
+    fn _end_lease(kitty_id: T::Hash, lease: Lease<T::AccountId, T::Balance, T::BlockNumber>) -> Result {
+        // Verify first, write lastの原則：ロックを外す前に、貸し手へ返却できることを確認する。
+        Self::_ensure_can_transfer_locked(&lease.lessee, &lease.lessor, kitty_id, LockReason::Lease)?;
+
+        Self::unlock_kitty(kitty_id);
+        Self::_transfer_from(lease.lessee.clone(), lease.lessor.clone(), kitty_id)?;
+        <Leases<T>>::remove(kitty_id);
+
+        if <system::Module<T>>::block_number() >= lease.expiry {
+            Self::deposit_event(RawEvent::LeaseEnded(lease.lessor, lease.lessee, kitty_id));
+        } else {
+            Self::deposit_event(RawEvent::LeaseTerminated(lease.lessor, lease.lessee, kitty_id));
+        }
+
+        Ok(())
+    }
+
+    // `who`がkittyの所有者であることを確認する。存在しない・所有者がいない・他人のもの、をそれぞれ別のエラーで返す。
+    fn ensure_owner(who: &T::AccountId, kitty_id: T::Hash) -> Result {
+        let owner = Self::_existing_owner_of(kitty_id)?;
+        ensure!(owner == *who, Error::NotOwner.as_str());
+        Ok(())
+    }
+
+    // kittyの所有者を返す。存在しない場合と所有者がいない場合とで異なるエラーを返す。
+    fn _existing_owner_of(kitty_id: T::Hash) -> result::Result<T::AccountId, Error> {
+        match Self::kitty_status(kitty_id) {
+            KittyStatus::Nonexistent => Err(Error::KittyNotFound),
+            KittyStatus::Ownerless => Err(Error::NoOwner),
+            KittyStatus::Owned(owner) => Ok(owner),
+        }
+    }
+
+    // 新たなkittyを記録するヘルパー関数を用意。
+    // `origin_kind`はkittyの生まれ方で、`KittyCreated`イベントに載せる。
+    fn _mint(to: T::AccountId, kitty_id: T::Hash, new_kitty: Kitty<T::Hash, T::Balance>, genome: Vec<u8>, origin_kind: KittyOrigin) -> Result {
+        // 計算したrandom_hashが衝突していないことを確認する。
+        ensure!(!<KittyOwner<T>>::exists(kitty_id), Error::KittyAlreadyExists.as_str());
+
+        // Verify first, write lastの原則：この人が現在何匹のkittyを所有しているかを取得する。
+        let owned_kitty_count = Self::owned_kitty_count(&to);
+
+        // Verify first, write lastの原則：新しいkittyを所有するので更新する。所有できる数を超えないことも確認する。
+        let new_owned_kitty_count = owned_kitty_count.checked_add(1).ok_or(Error::Overflow)?;
+        Self::_ensure_can_own(new_owned_kitty_count)?;
+
+        // Verify first, write lastの原則：現在登録されているkittiesの個体数を確認する。
+        let all_kitties_count = Self::all_kitties_count();
+
+        // Verify first, write lastの原則：これから登録しようとしているkittyを追加してoverflowしないかを確認する。
+        let new_all_kitties_count = all_kitties_count.checked_add(1).ok_or(Error::Overflow)?;
+
+        // Verify first, write lastの原則：nonceはどの経路でmintしてもここで1つ進める。上限に達したらpanicせずにエラーを返す。
+        let new_nonce = <Nonce<T>>::get().checked_add(1).ok_or(Error::Overflow)?;
+
+        // Verify first, write lastの原則：この世代のkittyの数がoverflowしないかを確認する。
+        let gen = new_kitty.gen;
+        let new_kitties_in_gen = Self::kitties_in_gen(gen).checked_add(1).ok_or(Error::Overflow)?;
+        Self::_ensure_generation_has_room(gen, 1)?;
+
+        // (random_hash, new_kitty)を登録する。
+        let dna = new_kitty.dna;
+        <Kitties<T>>::insert(kitty_id, new_kitty);
+        <Genomes<T>>::insert(kitty_id, &genome);
+
+        // 最初の所有者を作者として記録する。作者は後から変わらない。
+        <KittyCreator<T>>::insert(kitty_id, &to);
+
+        // (生成者を一意に区別するハッシュ値, 生成者)を登録する。
+        <KittyOwner<T>>::insert(kitty_id, &to);
+
+        // (all_kitties_count, random_hash)と(random_hash, all_kitties_count)を登録する。all_kitties_countは0オリジンの通し番号となる。
+        if T::ENUMERATE_ALL_KITTIES {
+            <AllKittiesArray<T>>::insert(all_kitties_count, kitty_id);
+            <AllKittiesIndex<T>>::insert(kitty_id, all_kitties_count);
+        }
+
+        // 「現在のkittiesの個体数」を更新する。
+        <AllKittiesCount<T>>::put(new_all_kitties_count);
+
+        // nonceを進める。
+        <Nonce<T>>::put(new_nonce);
+
+        // // (生成者, 生成者を一意に区別するハッシュ値)を登録する。
+        // <OwnedKitty<T>>::insert(&sender, random_hash);
+
+        // ユーザーの連結リストの末尾にkittyを追加する。
+        Self::_append_owned_kitty(&to, kitty_id);
+
+        // (ユーザー, ユーザーの所有しているkittyの個体数)を登録する。
+        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count);
+
+        // 一度でも存在したことと、そのDNAを記録する。
+        <EverExisted<T>>::insert(kitty_id, true);
+        Self::_record_dna(dna);
+
+        // 世代ごとの数と一覧、最も新しい世代を更新する。移行中なら、移行で数え直さないよう記録しておく。
+        <KittiesPerGen<T>>::insert(gen, new_kitties_in_gen);
+        <KittiesByGeneration<T>>::mutate(gen, |kitties| kitties.push(kitty_id));
+        if gen > Self::highest_gen() {
+            <HighestGen<T>>::put(gen);
+        }
+        if Self::storage_version() < CURRENT_STORAGE_VERSION {
+            <GenCounted<T>>::insert(kitty_id, true);
+        }
+
+        // 最初の所有者を来歴に記録する。
+        Self::_record_transfer(kitty_id, to.clone());
+
+        T::OnKittyTransfer::on_mint(&to, &kitty_id);
+
+        // トランザクション執行後のイベントを吐く。
+        // `Created`は互換性のために残し、遺伝子と世代、生まれ方を含む`KittyCreated`も吐く。
+        Self::deposit_event(RawEvent::Created(to.clone(), kitty_id));
+        Self::deposit_event(RawEvent::KittyCreated(to.clone(), kitty_id, genome, gen, origin_kind, to));
+
+        Ok(())
+    }
+
+    // `seller`が`price`で売ったときの販売手数料を返す。累計販売額が下限以上の段階のうち最も高い段階の率を使う。
+    // 率は100%以下なので、手数料が代金を超えることはない。
+    pub fn sale_fee(seller: &T::AccountId, price: T::Balance) -> T::Balance {
+        let volume = Self::seller_volume(seller);
+        let rate = Self::sale_fee_tiers().into_iter()
+            .take_while(|(threshold, _)| *threshold <= volume)
+            .last()
+            .map_or(0, |(_, rate)| cmp::min(rate, SALE_FEE_DENOMINATOR));
+
+        Self::_apply_rate(price, rate)
+    }
+
+    // `seller`が`kitty_id`を`price`で売ったときに作者へ払うロイヤリティと、その作者を返す。
+    // 作者が売り手のときや、ロイヤリティがないときは`None`を返す。
+    pub fn royalty_of(kitty_id: T::Hash, seller: &T::AccountId, price: T::Balance) -> Option<(T::AccountId, T::Balance)> {
+        let royalty = Self::royalty_rate_of(kitty_id);
+        if royalty == 0 {
+            return None;
+        }
+        let creator = Self::creator_of(kitty_id)?;
+        if creator == *seller {
+            return None;
+        }
+
+        let amount = Self::_apply_rate(price, cmp::min(royalty, MAX_ROYALTY));
+        if amount.is_zero() {
+            return None;
+        }
+
+        Some((creator, amount))
+    }
+
+    // `price`に1万分率の`rate`を掛けた額を切り捨てで返す。率が100%以下なら`price`を超えない。
+    fn _apply_rate(price: T::Balance, rate: u32) -> T::Balance {
+        // 桁あふれしないよう、分母で割ってから率を掛ける。
+        let rate = <T::Balance as As<u64>>::sa(rate as u64);
+        let denominator = <T::Balance as As<u64>>::sa(SALE_FEE_DENOMINATOR as u64);
+        price / denominator * rate + price % denominator * rate / denominator
+    }
+
+    // `who`に`amount`を送っても、口座が作れないために送金が失敗することがないかを確認する。
+    fn _ensure_can_receive(who: &T::AccountId, amount: T::Balance) -> Result {
+        ensure!(
+            !<balances::Module<T>>::free_balance(who).is_zero() || amount >= <balances::Module<T>>::existential_deposit(),
+            "Error: the payment is too small to open the recipient's account"
+        );
+
+        Ok(())
+    }
+
+    // `seller`が`kitty_id`を`price`で売ったときの代金の分け方を求め、手数料とロイヤリティを受け取れることを確認する。
+    // ロイヤリティは手数料と合わせて代金を超えないよう切り詰める。
+    fn _sale_split(kitty_id: T::Hash, seller: &T::AccountId, price: T::Balance) -> result::Result<SaleSplit<T::AccountId, T::Balance>, &'static str> {
+        let sale_fee = Self::sale_fee(seller, price);
+        let fee = if sale_fee.is_zero() {
+            None
+        } else {
+            let treasury = Self::treasury().ok_or(Error::NoTreasury)?;
+            Self::_ensure_can_receive(&treasury, sale_fee)?;
+            Some((treasury, sale_fee))
+        };
+
+        let royalty = match Self::royalty_of(kitty_id, seller, price) {
+            Some((creator, royalty)) => {
+                let royalty = cmp::min(royalty, price - sale_fee);
+                if royalty.is_zero() {
+                    None
+                } else {
+                    Self::_ensure_can_receive(&creator, royalty)?;
+                    Some((creator, royalty))
+                }
+            }
+            None => None,
+        };
+        let royalty_amount = royalty.as_ref().map_or(Zero::zero(), |(_, royalty)| *royalty);
+
+        Ok(SaleSplit { proceeds: price - sale_fee - royalty_amount, fee, royalty })
+    }
+
+    // 使える額が`available`の`payer`が、自由残高から`split`のとおりに代金を払えることを確認する。
+    // 代金を分けて送るときは、1回目の後で残りが失敗しないよう存在保証金を残させる。
+    fn _ensure_can_pay_sale(payer: &T::AccountId, available: T::Balance, seller: &T::AccountId, price: T::Balance, split: &SaleSplit<T::AccountId, T::Balance>) -> Result {
+        let remaining = available.checked_sub(&price).ok_or("Error: you do not have enough free balance to pay the price")?;
+        if split.fee.is_some() || split.royalty.is_some() {
+            ensure!(remaining >= <balances::Module<T>>::existential_deposit(), "Error: paying the price would drop your balance below the existential deposit");
+        }
+        if !split.proceeds.is_zero() {
+            Self::_ensure_can_receive(seller, split.proceeds)?;
+        }
+
+        Ok(())
+    }
+
+    // `payer`の自由残高から`split`のとおりに代金を払う。`_ensure_can_pay_sale`で確認してから呼ぶ。
+    fn _pay_sale(payer: &T::AccountId, seller: &T::AccountId, kitty_id: T::Hash, split: SaleSplit<T::AccountId, T::Balance>) -> Result {
+        if !split.proceeds.is_zero() {
+            <balances::Module<T>>::make_transfer(payer, seller, split.proceeds)?;
+        }
+        if let Some((treasury, fee)) = split.fee {
+            <balances::Module<T>>::make_transfer(payer, &treasury, fee)?;
+            Self::deposit_event(RawEvent::SaleFeePaid(treasury, kitty_id, fee));
+        }
+        if let Some((creator, royalty)) = split.royalty {
+            <balances::Module<T>>::make_transfer(payer, &creator, royalty)?;
+            Self::deposit_event(RawEvent::RoyaltyPaid(creator, kitty_id, royalty));
+        }
+
+        Ok(())
+    }
+
+    // `payer`が確保している額から`split`のとおりに代金を払えることを確認する。
+    // 確保した額を移す先は、すでに口座がなければならない。
+    fn _ensure_can_pay_sale_from_reserved(payer: &T::AccountId, seller: &T::AccountId, price: T::Balance, split: &SaleSplit<T::AccountId, T::Balance>) -> Result {
+        ensure!(<balances::Module<T>>::reserved_balance(payer) >= price, "Error: the reserved balance does not cover the price");
+        if let Some((treasury, _)) = &split.fee {
+            ensure!(!<balances::Module<T>>::total_balance(treasury).is_zero(), "Error: the treasury account does not exist");
+        }
+        if let Some((creator, _)) = &split.royalty {
+            ensure!(!<balances::Module<T>>::total_balance(creator).is_zero(), "Error: the creator account does not exist");
+        }
+        if !split.proceeds.is_zero() {
+            ensure!(!<balances::Module<T>>::total_balance(seller).is_zero(), "Error: the seller account does not exist");
+        }
+
+        Ok(())
+    }
+
+    // `payer`が確保している額から`split`のとおりに代金を払う。`_ensure_can_pay_sale_from_reserved`で確認してから呼ぶ。
+    // 手数料を払えなければ、何も動かさずにエラーを返す。
+    fn _pay_sale_from_reserved(payer: &T::AccountId, seller: &T::AccountId, kitty_id: T::Hash, split: SaleSplit<T::AccountId, T::Balance>) -> Result {
+        if let Some((treasury, fee)) = split.fee {
+            <balances::Module<T>>::repatriate_reserved(payer, &treasury, fee)?;
+            Self::deposit_event(RawEvent::SaleFeePaid(treasury, kitty_id, fee));
+        }
+        if let Some((creator, royalty)) = split.royalty {
+            <balances::Module<T>>::repatriate_reserved(payer, &creator, royalty)?;
+            Self::deposit_event(RawEvent::RoyaltyPaid(creator, kitty_id, royalty));
+        }
+        if !split.proceeds.is_zero() {
+            <balances::Module<T>>::repatriate_reserved(payer, seller, split.proceeds)?;
+        }
+
+        Ok(())
+    }
+
+    // 売買が成立したときに呼び、売り手の累計販売額に加え、過去最高額を超えていれば記録を更新する。
+    fn _record_sale(kitty_id: T::Hash, buyer: &T::AccountId, seller: &T::AccountId, price: T::Balance) {
+        <SellerVolume<T>>::mutate(seller, |volume| *volume = volume.saturating_add(price));
+
+        // 累計の売買額は指標なので、桁あふれするならそこで数えるのをやめる。売買そのものは止めない。
+        if let Some(total_volume) = Self::total_volume().checked_add(&price) {
+            <TotalVolume<T>>::put(total_volume);
+        }
+
+        let is_record = Self::highest_sale().map_or(true, |(_, _, _, highest_price, _)| price > highest_price);
+        if is_record {
+            let now = <system::Module<T>>::block_number();
+            <HighestSale<T>>::put((kitty_id, buyer.clone(), seller.clone(), price, now));
+        }
+    }
+
+    // `who`が`kitty_id`のpriceを`new_price`に設定できるかを確認する。書き込みは行わない。
+    fn _ensure_can_set_price(who: &T::AccountId, kitty_id: T::Hash, new_price: T::Balance) -> Result {
+        // 指定したkittyが存在し、本当にそのkittyはあなたのもの？
+        Self::ensure_owner(who, kitty_id)?;
+        Self::_ensure_not_leased(kitty_id)?;
+        Self::_ensure_unlocked(kitty_id)?;
+
+        // 出品の取り下げは`unlist`で行う。価格を0にして取り下げることはできない。
+        ensure!(!new_price.is_zero(), Error::ZeroPrice.as_str());
+
+        // 最低価格を下回らないことを確認する。
+        ensure!(new_price >= Self::price_floor(kitty_id), Error::BelowPriceFloor.as_str());
+
+        // 売買が無効なら出品できない。取り下げはいつでもできる。
+        Self::_ensure_market_enabled()?;
+
+        Ok(())
+    }
+
+    // kittyの出品価格を返す。出品されていなければ0を返す。
+    // 移行中は、まだ移行していないkittyの`Kitty`の`price`を読む。
+    pub fn price_of(kitty_id: T::Hash) -> T::Balance {
+        match Self::listing(kitty_id) {
+            Some(price) => price,
+            None if Self::storage_version() < CURRENT_STORAGE_VERSION => Self::kitty(kitty_id).price,
+            None => Zero::zero(),
+        }
+    }
+
+    // `price`に出品価格を入れたkittyを返す。`Kitty`の`price`を読むフロントエンドとランタイムAPIのためのビュー。
+    pub fn kitty_with_price(kitty_id: T::Hash) -> Kitty<T::Hash, T::Balance> {
+        let mut kitty = Self::kitty(kitty_id);
+        kitty.price = Self::price_of(kitty_id);
+        kitty
+    }
+
+    // kittyの出品価格を更新し、売りに出されているkittyの数を合わせる。`Kitties`は書き直さない。
+    fn _set_price(kitty_id: T::Hash, new_price: T::Balance) {
+        // 移行前の`price`が後から`Listings`を上書きしないよう、先に移行しておく。
+        Self::_ensure_migrated(kitty_id);
+
+        let was_for_sale = !Self::price_of(kitty_id).is_zero();
+        let is_for_sale = !new_price.is_zero();
+
+        if !was_for_sale && is_for_sale {
+            <ForSaleCount<T>>::mutate(|n| *n = n.saturating_add(1));
+        } else if was_for_sale && !is_for_sale {
+            <ForSaleCount<T>>::mutate(|n| *n = n.saturating_sub(1));
+        }
+
+        if is_for_sale {
+            <Listings<T>>::insert(kitty_id, new_price);
+            Self::_add_to_for_sale(kitty_id);
+
+            // 新たに売りに出されたkittyをウォッチしている人がいれば知らせる。値段の付け直しでは知らせない。
+            if !was_for_sale && <Watchers<T>>::exists(kitty_id) {
+                Self::deposit_event(RawEvent::WatchedKittyListed(kitty_id, new_price));
+            }
+        } else {
+            <Listings<T>>::remove(kitty_id);
+            Self::_remove_from_for_sale(kitty_id);
+        }
+
+        // 出品し直したり取り下げたりしたら、前の出品の期限は無効になる。
+        <ListingExpiry<T>>::remove(kitty_id);
+    }
+
+    // 売りに出されているkittyの一覧の末尾にkittyを加える。すでに載っていれば何もしない。
+    fn _add_to_for_sale(kitty_id: T::Hash) {
+        if <ForSaleIndex<T>>::exists(kitty_id) {
+            return;
+        }
+
+        let index = Self::for_sale_array_count();
+        <ForSaleArray<T>>::insert(index, kitty_id);
+        <ForSaleIndex<T>>::insert(kitty_id, index);
+        <ForSaleArrayCount<T>>::put(index.saturating_add(1));
+    }
+
+    // 売りに出されているkittyの一覧からkittyを取り除き、末尾のkittyを空いた位置に移して詰める。載っていなければ何もしない。
+    fn _remove_from_for_sale(kitty_id: T::Hash) {
+        if !<ForSaleIndex<T>>::exists(kitty_id) {
+            return;
+        }
+
+        let index = <ForSaleIndex<T>>::take(kitty_id);
+        let last_index = Self::for_sale_array_count().saturating_sub(1);
+        if index != last_index {
+            let last_kitty_id = <ForSaleArray<T>>::get(last_index);
+            <ForSaleArray<T>>::insert(index, last_kitty_id);
+            <ForSaleIndex<T>>::insert(last_kitty_id, index);
+        }
+
+        <ForSaleArray<T>>::remove(last_index);
+        <ForSaleArrayCount<T>>::put(last_index);
+    }
+
+    // 売りに出されているkittyの`start`番目から最大`limit`匹を、ハッシュ値と価格の組で返す。
+    // 範囲は一覧の長さと`MAX_PAGE_SIZE`で切り詰める。
+    pub fn kitties_for_sale(start: u64, limit: u64) -> Vec<(T::Hash, T::Balance)> {
+        let end = start.saturating_add(cmp::min(limit, MAX_PAGE_SIZE));
+        let end = cmp::min(end, Self::for_sale_array_count());
+
+        (start..end)
+            .map(|i| {
+                let kitty_id = Self::for_sale_by_index(i);
+                (kitty_id, Self::price_of(kitty_id))
+            })
+            .collect()
+    }
+
+    // 出品の期限が過ぎているかを返す。期限のない出品は期限切れにならない。
+    fn _listing_expired(kitty_id: T::Hash) -> bool {
+        match Self::listing_expiry(kitty_id) {
+            Some(expiry) => <system::Module<T>>::block_number() > expiry,
+            None => false,
+        }
+    }
+
+    // 全kittyのリストからkittyを取り除くヘルパー関数。
+    // 所有者ごとの連結リストと違い、Swap and Popメソッドで末尾のkittyを空いた位置に移して詰める。
+    // 通し番号を管理していなければ個体数を減らすだけである。
+    fn _remove_from_all(kitty_id: T::Hash) -> Result {
+        let last_index = Self::all_kitties_count().checked_sub(1)
+            .ok_or(Error::Underflow)?;
+
+        if !T::ENUMERATE_ALL_KITTIES {
+            <AllKittiesCount<T>>::put(last_index);
+            return Ok(());
+        }
+
+        ensure!(<AllKittiesIndex<T>>::exists(kitty_id), "Error: this kitty is not in the list of all kitties");
+        let kitty_index = <AllKittiesIndex<T>>::get(kitty_id);
+
+        if kitty_index != last_index {
+            let last_kitty_id = <AllKittiesArray<T>>::get(last_index);
+            // 移行済みの位置に移るkittyは、移行から漏れないようにここで移行しておく。
+            Self::_ensure_migrated(last_kitty_id);
+            <AllKittiesArray<T>>::insert(kitty_index, last_kitty_id);
+            <AllKittiesIndex<T>>::insert(last_kitty_id, kitty_index);
+        }
+
+        <AllKittiesArray<T>>::remove(last_index);
+        <AllKittiesIndex<T>>::remove(kitty_id);
+        <AllKittiesCount<T>>::put(last_index);
+
+        Ok(())
+    }
+
+    // 所有者のリストからkittyを取り除くヘルパー関数。
+    fn _remove_from_owner(owner: &T::AccountId, kitty_id: T::Hash) -> Result {
+        let new_owned_kitty_count = Self::owned_kitty_count(owner).checked_sub(1)
+            .ok_or(Error::Underflow)?;
+
+        Self::_ensure_migrated(kitty_id);
+        Self::_unlink_owned_kitty(owner, kitty_id);
+        <OwnedKittiesCount<T>>::insert(owner, new_owned_kitty_count);
+
+        Ok(())
+    }
+
+    // 連結リストの末尾にkittyを追加する。
+    fn _append_owned_kitty(owner: &T::AccountId, kitty_id: T::Hash) {
+        let head_key = (owner.clone(), None);
+        let last = Self::owned_kitties_list(&head_key).unwrap_or_default().prev;
+
+        <OwnedKittiesList<T>>::insert((owner.clone(), Some(kitty_id)), KittyLinkedItem { prev: last, next: None });
+
+        // 末尾（リストが空なら番兵）の次を新しいkittyにする。
+        <OwnedKittiesList<T>>::mutate((owner.clone(), last), |item| {
+            let mut linked = item.take().unwrap_or_default();
+            linked.next = Some(kitty_id);
+            *item = Some(linked);
+        });
+
+        // 番兵の前（末尾）を新しいkittyにする。
+        <OwnedKittiesList<T>>::mutate(head_key, |item| {
+            let mut linked = item.take().unwrap_or_default();
+            linked.prev = Some(kitty_id);
+            *item = Some(linked);
+        });
+    }
+
+    // 連結リストからkittyを外し、前後をつなぎ直す。リストにないkittyなら何もしない。
+    fn _unlink_owned_kitty(owner: &T::AccountId, kitty_id: T::Hash) {
+        let item = match <OwnedKittiesList<T>>::take((owner.clone(), Some(kitty_id))) {
+            Some(item) => item,
+            None => return,
+        };
+
+        <OwnedKittiesList<T>>::mutate((owner.clone(), item.prev), |prev| {
+            if let Some(prev) = prev {
+                prev.next = item.next;
+            }
+        });
+        <OwnedKittiesList<T>>::mutate((owner.clone(), item.next), |next| {
+            if let Some(next) = next {
+                next.prev = item.prev;
+            }
+        });
+
+        // 空になったリストの番兵は消しておく。
+        let head_key = (owner.clone(), None);
+        if Self::owned_kitties_list(&head_key).map_or(false, |head| head.next.is_none()) {
+            <OwnedKittiesList<T>>::remove(head_key);
+        }
+    }
+
+    // `reason`のロックが掛かったkittyについて、ロックを外せば`from`から`to`へ転送できるかを確認する。書き込みは行わない。
+    // オークションなど、ロックを掛けた処理の中で代金を動かしてから転送するときに使う。
+    fn _ensure_can_transfer_locked(from: &T::AccountId, to: &T::AccountId, kitty_id: T::Hash, reason: LockReason) -> Result {
+        if let Some(lock) = Self::kitty_lock(kitty_id) {
+            ensure!(lock == reason, lock.as_str());
+        }
+        Self::_ensure_can_move(from, to, kitty_id)?;
+
+        Ok(())
+    }
+
+    // `from`から`to`へ`kitty_id`を転送できるかを確認する。書き込みは行わない。
+    // 代金を動かしてからkittyを転送する処理は、先にこれで確認して転送が途中で失敗しないようにする。
+    // 転送後の双方の個体数と、kittyとともに移る預り金を返す。
+    fn _ensure_can_transfer(from: &T::AccountId, to: &T::AccountId, kitty_id: T::Hash) -> result::Result<(u64, u64, T::Balance), &'static str> {
+
+        // Verify first, write lastの原則：ロックされたkittyはどの経路からも転送できない。
+        Self::_ensure_unlocked(kitty_id)?;
+
+        Self::_ensure_can_move(from, to, kitty_id)
+    }
+
+    // ロック以外の、`from`から`to`へ`kitty_id`を転送するための条件を確認する。返す値は`_ensure_can_transfer`と同じ。
+    fn _ensure_can_move(from: &T::AccountId, to: &T::AccountId, kitty_id: T::Hash) -> result::Result<(u64, u64, T::Balance), &'static str> {
+
+        // Verify first, write lastの原則：呼び出し元が転送したいkittyの所有者であるかを確認する。
+        let owner = Self::owner_of(kitty_id).ok_or(Error::NoOwner)?;
+        ensure!(owner == *from, Error::NotOwner.as_str());
+
+        // Verify first, write lastの原則：自分自身への転送は個体数の更新が重なって壊れるので拒否する。
+        ensure!(from != to, Error::SelfTransfer.as_str());
+
+        // 転送元が何匹のkittyを所有しているかを確認する。
+        let owned_kitty_count_from = Self::owned_kitty_count(from);
+
+        // 転送先が何匹のkittyを所有しているかを確認する。
+        let owned_kitty_count_to = Self::owned_kitty_count(to);
+
+        // 転送先がすでにn匹のkittyを所有しているならば、転送先ではn+1匹目として扱われることを確認する。
+        let new_owned_kitty_count_to = owned_kitty_count_to.checked_add(1).ok_or(Error::Overflow)?;
+        Self::_ensure_can_own(new_owned_kitty_count_to)?;
+
+        // 転送元がn匹のkittyを所有しているならば、転送してしまうと所有している個体数が1減ることを確認する。
+        let new_owned_kitty_count_from = owned_kitty_count_from.checked_sub(1).ok_or(Error::Underflow)?;
+
+        // 預り金はkittyとともに移るので、転送元にその額が確保されていることを確認する。
+        let deposit = Self::deposit_of(kitty_id);
+        ensure!(<balances::Module<T>>::reserved_balance(from) >= deposit, Error::DepositNotReserved.as_str());
+
+        // 預り金は転送先へ移してから確保し直すので、途中で失敗しないよう転送先の口座があり、確保できることを確認する。
+        if !deposit.is_zero() {
+            ensure!(!<balances::Module<T>>::total_balance(to).is_zero(), Error::NoDepositAccount.as_str());
+            <T as balances::Trait>::EnsureAccountLiquid::ensure_account_liquid(to)?;
+        }
+
+        Ok((new_owned_kitty_count_from, new_owned_kitty_count_to, deposit))
+    }
+
+    // 転送元と転送先、転送されるkittyを特定するハッシュ値を引数に、転送を実行しその成否を返すヘルパー関数
+    fn _transfer_from(from: T::AccountId, to: T::AccountId, kitty_id: T::Hash) -> Result {
+
+        // Verify first, write lastの原則：書き込む前に、転送できることをすべて確認する。
+        let (new_owned_kitty_count_from, new_owned_kitty_count_to, deposit) = Self::_ensure_can_transfer(&from, &to, kitty_id)?;
+
+        // 預り金を転送元の確保分から転送先へ移し、転送先で確保し直す。
+        // 転送先の口座があり確保できることは確認済みで、移した額はそのまま転送先の自由残高にあるので確保は失敗しない。
+        if !deposit.is_zero() {
+            <balances::Module<T>>::repatriate_reserved(&from, &to, deposit)?;
+            <balances::Module<T>>::reserve(&to, deposit)?;
+        }
+
+        // 転送元の連結リストからkittyを外し、転送先の連結リストの末尾につなぐ。
+        // 他のkittyの順番は変わらない。
+        Self::_ensure_migrated(kitty_id);
+        Self::_unlink_owned_kitty(&from, kitty_id);
+        Self::_append_owned_kitty(&to, kitty_id);
+
+        // 転送されたkittyの所有者を更新する。
+        <KittyOwner<T>>::insert(&kitty_id, &to);
+
+        //  双方の持っている個体数を更新する。
+        <OwnedKittiesCount<T>>::insert(&from, new_owned_kitty_count_from);
+        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count_to);
+
+        // 種付け料は前の所有者の同意なので、所有者が変わったら取り消す。
+        <StudFee<T>>::remove(kitty_id);
+
+        // 出品と最低価格も取り消す。自分のアカウント間で移すときなど、転送元が望めば残す。
+        if !Self::keep_listings_on_transfer(&from) {
+            Self::_set_price(kitty_id, <T::Balance as As<u64>>::sa(0));
+            <PriceFloor<T>>::remove(kitty_id);
+        }
+
+        // 新しい所有者を来歴に記録する。
+        Self::_record_transfer(kitty_id, to.clone());
+
+        T::OnKittyTransfer::on_transfer(&from, &to, &kitty_id);
+
+        // Transferredイベントを吐く。
+        Self::deposit_event(RawEvent::Transferred(from, to, kitty_id));
+
+        Ok(())
+    }
+
+    // kittyの来歴に新しい所有者を追加する。上限に達していたら最も古い記録を捨てる。
+    fn _record_transfer(kitty_id: T::Hash, new_owner: T::AccountId) {
+        let now = <system::Module<T>>::block_number();
+        <TransferHistory<T>>::mutate(kitty_id, |history| {
+            if history.len() >= MAX_TRANSFER_HISTORY {
+                history.remove(0);
+            }
+            history.push((new_owner, now));
+        });
+    }
+
+    // 所有権の記録が互いに矛盾していないことを確認する。テストやベンチマークで操作の後に呼ぶ。
+    // 全kittyの通し番号と`AllKittiesIndex`が一致し、所有者ごとの連結リストの前後のリンクと`KittyOwner`が一致し、
+    // リストの長さが`OwnedKittiesCount`と、その合計が所有者のいるkittyの数と一致すること。
+    // 全kittyをたどるので、通し番号を管理していてストレージの移行が終わっているときだけ確認する。
+    #[cfg(any(test, feature = "try-runtime"))]
+    pub fn verify_invariants() -> Result {
+        if !T::ENUMERATE_ALL_KITTIES || Self::storage_version() < CURRENT_STORAGE_VERSION {
+            return Ok(());
+        }
+
+        let mut owners: Vec<T::AccountId> = Vec::new();
+        let mut owned_kitties = 0u64;
+        for i in 0..Self::all_kitties_count() {
+            let kitty_id = Self::kitty_by_index(i);
+            ensure!(<AllKittiesIndex<T>>::get(kitty_id) == i, "Invariant: AllKittiesIndex does not match AllKittiesArray");
+            ensure!(<Kitties<T>>::exists(kitty_id), "Invariant: AllKittiesArray points to a kitty that does not exist");
+            if let Some(owner) = Self::owner_of(kitty_id) {
+                ensure!(<OwnedKittiesList<T>>::exists((owner.clone(), Some(kitty_id))), "Invariant: an owned kitty is missing from its owner's list");
+                owned_kitties += 1;
+                if !owners.contains(&owner) {
+                    owners.push(owner);
+                }
+            }
+        }
+
+        let mut counted_kitties = 0u64;
+        for owner in owners {
+            let mut length = 0u64;
+            let mut prev = None;
+            let mut cursor = Self::owned_kitties_list((owner.clone(), None)).and_then(|head| head.next);
+            while let Some(kitty_id) = cursor {
+                let item = Self::owned_kitties_list((owner.clone(), Some(kitty_id)))
+                    .ok_or("Invariant: an owner's list links to a missing item")?;
+                ensure!(item.prev == prev, "Invariant: an owner's list has an inconsistent prev link");
+                ensure!(Self::owner_of(kitty_id).as_ref() == Some(&owner), "Invariant: an owner's list contains a kitty owned by someone else");
+                length += 1;
+                ensure!(length <= Self::all_kitties_count(), "Invariant: an owner's list contains a cycle");
+                prev = Some(kitty_id);
+                cursor = item.next;
+            }
+            let tail = Self::owned_kitties_list((owner.clone(), None)).and_then(|head| head.prev);
+            ensure!(tail == prev, "Invariant: an owner's list has an inconsistent tail");
+            ensure!(length == Self::owned_kitty_count(&owner), "Invariant: OwnedKittiesCount does not match the owner's list");
+            counted_kitties += length;
+        }
+        ensure!(counted_kitties == owned_kitties, "Invariant: the owned kitty counts do not add up");
+
+        // 売りに出されているkittyの一覧と出品が一致すること。
+        ensure!(Self::for_sale_array_count() == Self::for_sale_count(), "Invariant: ForSaleArrayCount does not match ForSaleCount");
+        for i in 0..Self::for_sale_array_count() {
+            let kitty_id = Self::for_sale_by_index(i);
+            ensure!(<ForSaleIndex<T>>::exists(kitty_id) && <ForSaleIndex<T>>::get(kitty_id) == i, "Invariant: ForSaleIndex does not match ForSaleArray");
+            ensure!(<Listings<T>>::exists(kitty_id), "Invariant: ForSaleArray contains a kitty that is not listed");
+        }
+
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use runtime_io::with_externalities;
+    use primitives::{H256, Blake2Hasher};
+    use support::{impl_outer_origin, assert_ok, assert_noop};
+    use runtime_primitives::{
+        BuildStorage,
+        traits::{BlakeTwo256, IdentityLookup, OnFinalise},
+        testing::{Digest, DigestItem, Header},
+    };
+    use system::RawOrigin;
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    // テスト用のランタイム。
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<u64>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+
+    impl balances::Trait for Test {
+        type Balance = u64;
+        type OnFreeBalanceZero = Module<Test>;
+        type OnNewAccount = ();
+        type EnsureAccountLiquid = ();
+        type Event = ();
+    }
+
+    impl Trait for Test {
+        type Event = ();
+        type Randomness = TestRandomness;
+        const ENUMERATE_ALL_KITTIES: bool = true;
+        const GENOME_LENGTH: u32 = 32;
+        const TRANSFER_FEE: u64 = 0;
+        const GEN0_CAP: u64 = 10;
+        type OnKittyTransfer = RecordingHook;
+    }
+
+    impl quest::Trait for Test {
+        type Items = Module<Test>;
+    }
+
+    type Substratekitties = Module<Test>;
+    type Balances = balances::Module<Test>;
+    type Quest = quest::Module<Test>;
+
+    thread_local! {
+        static RANDOM_SEED: RefCell<u64> = RefCell::new(0);
+        static HOOK_CALLS: RefCell<Vec<HookCall>> = RefCell::new(Vec::new());
+    }
+
+    // `OnKittyTransfer`が呼ばれた記録。
+    #[derive(Debug, PartialEq)]
+    enum HookCall {
+        Mint(u64, H256),
+        Transfer(u64, u64, H256),
+        Burn(u64, H256),
+    }
+
+    // 呼ばれたフックを`HOOK_CALLS`に記録する。
+    pub struct RecordingHook;
+
+    impl OnKittyTransfer<u64, H256> for RecordingHook {
+        fn on_mint(to: &u64, kitty_id: &H256) {
+            HOOK_CALLS.with(|calls| calls.borrow_mut().push(HookCall::Mint(*to, *kitty_id)));
+        }
+        fn on_transfer(from: &u64, to: &u64, kitty_id: &H256) {
+            HOOK_CALLS.with(|calls| calls.borrow_mut().push(HookCall::Transfer(*from, *to, *kitty_id)));
+        }
+        fn on_burn(owner: &u64, kitty_id: &H256) {
+            HOOK_CALLS.with(|calls| calls.borrow_mut().push(HookCall::Burn(*owner, *kitty_id)));
+        }
+    }
+
+    fn take_hook_calls() -> Vec<HookCall> {
+        HOOK_CALLS.with(|calls| calls.replace(Vec::new()))
+    }
+
+    // `NonFungible`だけを通してkittyを扱う、別のモジュールの代わり。
+    mod quest {
+        use super::super::NonFungible;
+        use support::{decl_module, dispatch::Result, ensure};
+        use system::{ensure_signed, ensure_root};
+
+        pub trait Trait: system::Trait {
+            type Items: NonFungible<ItemId = Self::Hash, AccountId = Self::AccountId>;
+        }
+
+        decl_module! {
+            pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+                // 自分のアイテムを`to`に渡す。
+                pub fn give(origin, to: T::AccountId, id: T::Hash) -> Result {
+                    let sender = ensure_signed(origin)?;
+                    ensure!(T::Items::owner(id) == Some(sender.clone()), "Error: you do not own this item");
+                    T::Items::transfer(sender, to, id)
+                }
+
+                // rootが`to`に報酬のアイテムを与える。
+                pub fn reward(origin, to: T::AccountId, id: T::Hash, dna: T::Hash) -> Result {
+                    ensure_root(origin)?;
+                    T::Items::mint(to, id, dna)
+                }
+
+                // rootがアイテムを取り上げる。
+                pub fn confiscate(origin, id: T::Hash) -> Result {
+                    ensure_root(origin)?;
+                    T::Items::burn(id)
+                }
+            }
+        }
+    }
+
+    // `RANDOM_SEED`と`subject`だけから決まる乱数を返す。
+    pub struct TestRandomness;
+
+    impl Randomness<H256> for TestRandomness {
+        fn random(subject: &[u8]) -> H256 {
+            (RANDOM_SEED.with(|seed| *seed.borrow()), subject).using_encoded(BlakeTwo256::hash)
+        }
+    }
+
+    const ALICE: u64 = 1;
+    const BOB: u64 = 2;
+    const TREASURY: u64 = 3;
+    const POUND: u64 = 4;
+    const ENDOWMENT: u64 = 1_000;
+
+    fn default_config() -> GenesisConfig<Test> {
+        GenesisConfig::<Test> {
+            creation_fee: 0,
+            max_kitties_owned: 0,
+            create_cooldown: 0,
+            base_breeding_cooldown: 0,
+        }
+    }
+
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        new_test_ext_with(default_config())
+    }
+
+    // ALICE、BOB、TREASURY、POUNDに`ENDOWMENT`ずつ持たせ、ストレージを最新のバージョンで始める。
+    fn new_test_ext_with(config: GenesisConfig<Test>) -> runtime_io::TestExternalities<Blake2Hasher> {
+        RANDOM_SEED.with(|seed| *seed.borrow_mut() = 0);
+        take_hook_calls();
+        let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+        t.extend(balances::GenesisConfig::<Test> {
+            transaction_base_fee: 0,
+            transaction_byte_fee: 0,
+            existential_deposit: 1,
+            transfer_fee: 0,
+            creation_fee: 0,
+            balances: vec![(ALICE, ENDOWMENT), (BOB, ENDOWMENT), (TREASURY, ENDOWMENT), (POUND, ENDOWMENT)],
+            vesting: vec![],
+        }.build_storage().unwrap().0);
+        t.extend(config.build_storage().unwrap().0);
+        let mut ext: runtime_io::TestExternalities<Blake2Hasher> = t.into();
+        with_externalities(&mut ext, || <StorageVersion<Test>>::put(CURRENT_STORAGE_VERSION));
+        ext
+    }
+
+    fn root() -> Origin {
+        RawOrigin::Root.into()
+    }
+
+    // `who`にkittyを1匹生成し、そのidを返す。
+    fn create(who: u64) -> H256 {
+        assert_ok!(Substratekitties::create_kitty(Origin::signed(who)));
+        Substratekitties::kitty_of_owner_by_index((who, Substratekitties::owned_kitty_count(&who) - 1))
+    }
+
+    #[test]
+    fn create_kitty_fails_with_not_minter_when_the_allowlist_is_enabled() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_mint_allowlist_enabled(root(), true));
+            assert_noop!(Substratekitties::create_kitty(Origin::signed(ALICE)), Error::NotMinter.as_str());
+        });
+    }
+
+    #[test]
+    fn create_kitty_fails_with_create_cooldown_until_the_cooldown_has_passed() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_create_cooldown(root(), 5));
+            create(ALICE);
+            assert_noop!(Substratekitties::create_kitty(Origin::signed(ALICE)), Error::CreateCooldown.as_str());
+
+            <system::Module<Test>>::set_block_number(5);
+            assert_ok!(Substratekitties::create_kitty(Origin::signed(ALICE)));
+        });
+    }
+
+    #[test]
+    fn create_kitties_fails_with_gen0_cap_reached_beyond_the_cap() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::create_kitties(Origin::signed(ALICE), 10));
+            assert_noop!(Substratekitties::create_kitty(Origin::signed(BOB)), Error::Gen0CapReached.as_str());
+        });
+    }
+
+    #[test]
+    fn create_kitty_fails_with_too_many_kitties_owned_beyond_the_cap() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_max_kitties_owned(root(), 1));
+            create(ALICE);
+            assert_noop!(Substratekitties::create_kitty(Origin::signed(ALICE)), Error::TooManyKittiesOwned.as_str());
+        });
+    }
+
+    #[test]
+    fn create_kitty_fails_with_generation_full_when_the_generation_list_is_full() {
+        with_externalities(&mut new_test_ext(), || {
+            <KittiesByGeneration<Test>>::insert(0, vec![H256::zero(); MAX_KITTIES_PER_GENERATION]);
+            assert_noop!(Substratekitties::create_kitty(Origin::signed(ALICE)), Error::GenerationFull.as_str());
+        });
+    }
+
+    #[test]
+    fn breed_kitty_fails_with_kitty_staked_for_a_staked_parent() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            assert_ok!(Substratekitties::stake_kitty(Origin::signed(ALICE), kitty_1));
+            assert_noop!(Substratekitties::breed_kitty(Origin::signed(ALICE), kitty_1, kitty_2, None), Error::KittyStaked.as_str());
+        });
+    }
+
+    #[test]
+    fn breed_kitty_fails_with_litter_size_errors_outside_the_allowed_range() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            assert_noop!(Substratekitties::breed_kitty(Origin::signed(ALICE), kitty_1, kitty_2, Some(0)), Error::EmptyLitter.as_str());
+            assert_noop!(
+                Substratekitties::breed_kitty(Origin::signed(ALICE), kitty_1, kitty_2, Some(MAX_LITTER_SIZE + 1)),
+                Error::LitterTooLarge.as_str()
+            );
+        });
+    }
+
+    #[test]
+    fn set_price_fails_with_zero_price() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_noop!(Substratekitties::set_price(Origin::signed(ALICE), kitty_id, 0, None), Error::ZeroPrice.as_str());
+        });
+    }
+
+    #[test]
+    fn transfer_fails_with_deposit_not_reserved_once_the_deposit_is_gone() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_kitty_deposit(root(), 10));
+            let kitty_id = create(ALICE);
+            Balances::unreserve(&ALICE, 10);
+            assert_noop!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_id), Error::DepositNotReserved.as_str());
+        });
+    }
+
+    #[test]
+    fn kitty_status_is_nonexistent_for_an_unknown_id() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = H256::repeat_byte(1);
+            assert_eq!(Substratekitties::kitty_status(kitty_id), KittyStatus::Nonexistent);
+            assert_noop!(Substratekitties::set_price(Origin::signed(ALICE), kitty_id, 10, None), Error::KittyNotFound.as_str());
+            assert_noop!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_id), Error::KittyNotFound.as_str());
+            assert_noop!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_id, 10), Error::KittyNotFound.as_str());
+        });
+    }
+
+    #[test]
+    fn kitty_status_is_ownerless_for_a_kitty_without_an_owner() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = H256::repeat_byte(1);
+            <super::Kitties<Test>>::insert(kitty_id, Kitty { id: kitty_id, dna: kitty_id, price: 0, gen: 0 });
+            assert_eq!(Substratekitties::kitty_status(kitty_id), KittyStatus::Ownerless);
+            assert_noop!(Substratekitties::set_price(Origin::signed(ALICE), kitty_id, 10, None), Error::NoOwner.as_str());
+            assert_noop!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_id), Error::NoOwner.as_str());
+            assert_noop!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_id, 10), Error::NoOwner.as_str());
+        });
+    }
+
+    #[test]
+    fn kitty_status_is_owned_by_the_current_owner() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_eq!(Substratekitties::kitty_status(kitty_id), KittyStatus::Owned(ALICE));
+            assert_noop!(Substratekitties::set_price(Origin::signed(BOB), kitty_id, 10, None), Error::NotOwner.as_str());
+
+            assert_ok!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_id));
+            assert_eq!(Substratekitties::kitty_status(kitty_id), KittyStatus::Owned(BOB));
+        });
+    }
+
+    #[test]
+    fn migrate_storage_reads_old_format_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            // 所有者ごとの配列と`Kitty`の`price`に出品価格を持つ、バージョン1の形式で書き込む。
+            let kitty_id = H256::repeat_byte(1);
+            let dna = H256::repeat_byte(2);
+            runtime_io::set_storage(&<super::Kitties<Test>>::key_for(kitty_id), &(kitty_id, dna, 50u64, 0u64).encode());
+            runtime_io::set_storage(&<KittyOwner<Test>>::key_for(kitty_id), &ALICE.encode());
+            runtime_io::set_storage(&<AllKittiesArray<Test>>::key_for(0u64), &kitty_id.encode());
+            runtime_io::set_storage(&<AllKittiesIndex<Test>>::key_for(kitty_id), &0u64.encode());
+            runtime_io::set_storage(&<OwnedKittiesArray<Test>>::key_for((ALICE, 0u64)), &kitty_id.encode());
+            runtime_io::set_storage(&<OwnedKittiesIndex<Test>>::key_for(kitty_id), &0u64.encode());
+            <AllKittiesCount<Test>>::put(1);
+            <OwnedKittiesCount<Test>>::insert(ALICE, 1);
+            <StorageVersion<Test>>::put(0);
+
+            assert_ok!(Substratekitties::migrate_storage(root(), 10));
+
+            assert_eq!(Substratekitties::storage_version(), CURRENT_STORAGE_VERSION);
+            assert_eq!(Substratekitties::kitty(kitty_id).dna, dna);
+            assert_eq!(Substratekitties::kitty(kitty_id).price, 0);
+            assert_eq!(Substratekitties::listing(kitty_id), Some(50));
+            assert_eq!(Substratekitties::price_of(kitty_id), 50);
+            assert_eq!(Substratekitties::kitty_of_owner_by_index((ALICE, 0)), kitty_id);
+            assert!(!<OwnedKittiesIndex<Test>>::exists(kitty_id));
+            assert_eq!(Substratekitties::creator_of(kitty_id), Some(ALICE));
+            assert_eq!(Substratekitties::kitties_in_generation(0), vec![kitty_id]);
+
+            // 移行したkittyはそのまま売買できる。
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_id, 50));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+        });
+    }
+
+    #[test]
+    fn migrate_storage_resumes_across_batches() {
+        with_externalities(&mut new_test_ext(), || {
+            create(ALICE);
+            create(ALICE);
+            create(BOB);
+            <StorageVersion<Test>>::put(0);
+
+            assert_ok!(Substratekitties::migrate_storage(root(), 2));
+            assert_eq!(Substratekitties::storage_version(), 0);
+            assert_eq!(Substratekitties::migration_cursor(), 2);
+
+            assert_ok!(Substratekitties::migrate_storage(root(), 2));
+            assert_eq!(Substratekitties::storage_version(), CURRENT_STORAGE_VERSION);
+            assert_noop!(Substratekitties::migrate_storage(root(), 2), "Error: the storage is already up to date");
+        });
+    }
+
+    #[test]
+    fn kitty_ids_and_dna_come_from_the_injected_randomness() {
+        let expected = (TestRandomness::random(b"create_kitty"), &ALICE, 0u64).using_encoded(BlakeTwo256::hash);
+
+        let first = with_externalities(&mut new_test_ext(), || create(ALICE));
+        let second = with_externalities(&mut new_test_ext(), || create(ALICE));
+        assert_eq!(first, expected);
+        assert_eq!(second, expected);
+
+        with_externalities(&mut new_test_ext(), || {
+            RANDOM_SEED.with(|seed| *seed.borrow_mut() = 1);
+            let kitty_id = create(ALICE);
+            assert_ne!(kitty_id, expected);
+            assert_eq!(Substratekitties::kitty(kitty_id).dna, kitty_id);
+        });
+    }
+
+    #[test]
+    fn another_module_moves_kitties_through_non_fungible() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_noop!(Quest::give(Origin::signed(BOB), ALICE, kitty_id), "Error: you do not own this item");
+            assert_ok!(Quest::give(Origin::signed(ALICE), BOB, kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), 0);
+            assert_eq!(Substratekitties::owned_kitty_count(&BOB), 1);
+
+            let reward_id = H256::repeat_byte(7);
+            assert!(Quest::reward(Origin::signed(ALICE), ALICE, reward_id, reward_id).is_err());
+            assert_ok!(Quest::reward(root(), ALICE, reward_id, reward_id));
+            assert_eq!(Substratekitties::kitty_status(reward_id), KittyStatus::Owned(ALICE));
+            assert_noop!(Quest::reward(root(), BOB, reward_id, reward_id), Error::KittyAlreadyExists.as_str());
+
+            assert_ok!(Quest::confiscate(root(), reward_id));
+            assert_eq!(Substratekitties::kitty_status(reward_id), KittyStatus::Nonexistent);
+        });
+    }
+
+    #[test]
+    fn on_kitty_transfer_fires_for_mints_transfers_sales_and_burns() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            assert_eq!(take_hook_calls(), vec![HookCall::Mint(ALICE, kitty_1), HookCall::Mint(ALICE, kitty_2)]);
+
+            assert_ok!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_1));
+            assert_eq!(take_hook_calls(), vec![HookCall::Transfer(ALICE, BOB, kitty_1)]);
+
+            assert_ok!(Substratekitties::set_price(Origin::signed(BOB), kitty_1, 10, None));
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(ALICE), kitty_1, 10));
+            assert_eq!(take_hook_calls(), vec![HookCall::Transfer(BOB, ALICE, kitty_1)]);
+
+            assert_ok!(Substratekitties::breed_kitty(Origin::signed(ALICE), kitty_1, kitty_2, Some(2)));
+            let children = Substratekitties::children_of(kitty_1);
+            assert_eq!(children.len(), 2);
+            assert_eq!(take_hook_calls(), vec![HookCall::Mint(ALICE, children[0]), HookCall::Mint(ALICE, children[1])]);
+
+            assert_ok!(Substratekitties::burn_kitty(Origin::signed(ALICE), kitty_2));
+            assert_eq!(take_hook_calls(), vec![HookCall::Burn(ALICE, kitty_2)]);
+
+            // 失敗した呼び出しではフックは呼ばれない。
+            assert!(Substratekitties::transfer(Origin::signed(BOB), ALICE, kitty_1).is_err());
+            assert_eq!(take_hook_calls(), vec![]);
+        });
+    }
+
+    #[test]
+    fn genesis_config_sets_the_parameters() {
+        let config = GenesisConfig::<Test> {
+            creation_fee: 7,
+            max_kitties_owned: 3,
+            create_cooldown: 2,
+            base_breeding_cooldown: 4,
+        };
+        with_externalities(&mut new_test_ext_with(config), || {
+            assert_eq!(Substratekitties::creation_fee(), 7);
+            assert_eq!(Substratekitties::max_kitties_owned(), 3);
+            assert_eq!(Substratekitties::create_cooldown(), 2);
+            assert_eq!(Substratekitties::base_breeding_cooldown(), 4);
+        });
+    }
+
+    #[test]
+    fn parameter_setters_are_root_only() {
+        with_externalities(&mut new_test_ext(), || {
+            assert!(Substratekitties::set_creation_fee(Origin::signed(ALICE), 5).is_err());
+            assert!(Substratekitties::set_max_kitties_owned(Origin::signed(ALICE), 5).is_err());
+            assert!(Substratekitties::set_create_cooldown(Origin::signed(ALICE), 5).is_err());
+            assert!(Substratekitties::set_breeding_cooldown(Origin::signed(ALICE), 5, 0, 0).is_err());
+            assert_eq!(Substratekitties::creation_fee(), 0);
+            assert_eq!(Substratekitties::max_kitties_owned(), 0);
+            assert_eq!(Substratekitties::create_cooldown(), 0);
+            assert_eq!(Substratekitties::base_breeding_cooldown(), 0);
+
+            assert_ok!(Substratekitties::set_creation_fee(root(), 5));
+            assert_ok!(Substratekitties::set_max_kitties_owned(root(), 5));
+            assert_ok!(Substratekitties::set_create_cooldown(root(), 5));
+            assert_ok!(Substratekitties::set_breeding_cooldown(root(), 5, 0, 0));
+            assert_eq!(Substratekitties::creation_fee(), 5);
+            assert_eq!(Substratekitties::max_kitties_owned(), 5);
+            assert_eq!(Substratekitties::create_cooldown(), 5);
+            assert_eq!(Substratekitties::base_breeding_cooldown(), 5);
+        });
+    }
+
+    #[test]
+    fn sale_fee_rounds_down_on_a_one_unit_sale() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_breed_fee(root(), 0, Some(TREASURY), false));
+            assert_ok!(Substratekitties::set_sale_fee_tiers(root(), vec![(0, 250)]));
+            assert_eq!(Substratekitties::sale_fee(&ALICE, 1), 0);
+            assert_eq!(Substratekitties::sale_fee(&ALICE, 100), 2);
+
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_id, 1, None));
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_id, 1));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT + 1);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT - 1);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT);
+
+            assert_ok!(Substratekitties::set_price(Origin::signed(BOB), kitty_id, 100, None));
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(ALICE), kitty_id, 100));
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT - 1 + 98);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT + 2);
+        });
+    }
+
+    #[test]
+    fn set_sale_fee_tiers_rejects_a_full_rate() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_breed_fee(root(), 0, Some(TREASURY), false));
+            assert_noop!(
+                Substratekitties::set_sale_fee_tiers(root(), vec![(0, SALE_FEE_DENOMINATOR)]),
+                "Error: a sale fee rate must be below 100%"
+            );
+            assert_ok!(Substratekitties::set_sale_fee_tiers(root(), vec![(0, SALE_FEE_DENOMINATOR - 1)]));
+        });
+    }
+
+    #[test]
+    fn invariants_hold_after_mints_transfers_sales_and_burns() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::verify_invariants());
+
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            let kitty_3 = create(BOB);
+            assert_ok!(Substratekitties::verify_invariants());
+
+            // 先頭・末尾・唯一の要素をそれぞれ抜き差しする。
+            assert_ok!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_1));
+            assert_ok!(Substratekitties::transfer(Origin::signed(BOB), ALICE, kitty_3));
+            assert_ok!(Substratekitties::verify_invariants());
+
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_2, 10, None));
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_3, 10, None));
+            assert_ok!(Substratekitties::verify_invariants());
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_2, 10));
+            assert_ok!(Substratekitties::verify_invariants());
+
+            assert_ok!(Substratekitties::breed_kitty(Origin::signed(BOB), kitty_1, kitty_2, Some(3)));
+            assert_ok!(Substratekitties::verify_invariants());
+
+            assert_ok!(Substratekitties::burn_kitty(Origin::signed(BOB), kitty_1));
+            assert_ok!(Substratekitties::burn_kitty(Origin::signed(ALICE), kitty_3));
+            assert_ok!(Substratekitties::verify_invariants());
+            assert_eq!(Substratekitties::all_kitties_count(), 4);
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), 0);
+            assert_eq!(Substratekitties::owned_kitty_count(&BOB), 4);
+        });
+    }
+
+    #[test]
+    fn verify_invariants_detects_a_corrupted_index() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_1 = create(ALICE);
+            create(ALICE);
+            assert_ok!(Substratekitties::verify_invariants());
+
+            <AllKittiesIndex<Test>>::insert(kitty_1, 1);
+            assert!(Substratekitties::verify_invariants().is_err());
+        });
+    }
+
+    #[test]
+    fn verify_invariants_detects_a_wrong_owned_count() {
+        with_externalities(&mut new_test_ext(), || {
+            create(ALICE);
+            <OwnedKittiesCount<Test>>::insert(ALICE, 2);
+            assert!(Substratekitties::verify_invariants().is_err());
+        });
+    }
+
+    #[test]
+    fn gift_random_gives_the_kitty_picked_by_the_randomness() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitties = vec![create(ALICE), create(ALICE), create(ALICE)];
+            let nonce = <Nonce<Test>>::get();
+            let random_hash = (TestRandomness::random(b"gift_random"), &ALICE, nonce).using_encoded(BlakeTwo256::hash);
+            let random_value = u64::decode(&mut random_hash.as_ref()).unwrap();
+            let expected = kitties[(random_value % 3) as usize];
+
+            assert_ok!(Substratekitties::gift_random(Origin::signed(ALICE), BOB));
+            assert_eq!(Substratekitties::owner_of(expected), Some(BOB));
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), 2);
+        });
+    }
+
+    #[test]
+    fn gift_random_skips_locked_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let staked = create(ALICE);
+            let free = create(ALICE);
+            assert_ok!(Substratekitties::stake_kitty(Origin::signed(ALICE), staked));
+
+            assert_ok!(Substratekitties::gift_random(Origin::signed(ALICE), BOB));
+            assert_eq!(Substratekitties::owner_of(free), Some(BOB));
+            assert_eq!(Substratekitties::owner_of(staked), Some(ALICE));
+
+            assert_noop!(Substratekitties::gift_random(Origin::signed(ALICE), BOB), "Error: all of your kitties are leased or locked");
+        });
+    }
+
+    #[test]
+    fn gift_random_rejects_a_sender_without_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(Substratekitties::gift_random(Origin::signed(ALICE), BOB), "Error: you have no kitty to gift");
+        });
+    }
+
+    #[test]
+    fn create_kitties_takes_the_fee_and_reserves_the_deposits_up_front() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_breed_fee(root(), 0, Some(TREASURY), false));
+            assert_ok!(Substratekitties::set_creation_fee(root(), 5));
+            assert_ok!(Substratekitties::set_kitty_deposit(root(), 10));
+
+            assert_ok!(Substratekitties::create_kitties(Origin::signed(ALICE), 3));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT - 15 - 30);
+            assert_eq!(Balances::reserved_balance(&ALICE), 30);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT + 15);
+            let kitty_id = Substratekitties::kitty_of_owner_by_index((ALICE, 0));
+            assert_eq!(Substratekitties::deposit_of(kitty_id), 10);
+            assert_eq!(Substratekitties::gen0_count(), 3);
+        });
+    }
+
+    #[test]
+    fn create_kitties_writes_nothing_when_the_fee_can_not_be_paid() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_breed_fee(root(), 0, Some(TREASURY), false));
+            assert_ok!(Substratekitties::set_creation_fee(root(), 490));
+            assert_ok!(Substratekitties::set_kitty_deposit(root(), 10));
+
+            // 手数料と預り金で残高がちょうど0になる。
+            assert_noop!(
+                Substratekitties::create_kitties(Origin::signed(ALICE), 2),
+                "Error: paying the fee would drop your balance below the existential deposit"
+            );
+            assert_eq!(Substratekitties::gen0_count(), 0);
+            assert_eq!(Substratekitties::last_create_block(&ALICE), None);
+        });
+    }
+
+    #[test]
+    fn surrendered_kitties_can_be_adopted_for_the_fee() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_pound(root(), Some(POUND)));
+            assert_ok!(Substratekitties::set_breed_fee(root(), 0, Some(TREASURY), false));
+            assert_ok!(Substratekitties::set_adoption_fee(root(), 20));
+            let kitty_id = create(ALICE);
+
+            assert_ok!(Substratekitties::surrender_kitty(Origin::signed(ALICE), kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(POUND));
+            assert!(Substratekitties::is_adoptable(kitty_id));
+
+            assert_ok!(Substratekitties::adopt_kitty(Origin::signed(BOB), kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+            assert!(!Substratekitties::is_adoptable(kitty_id));
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT - 20);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT + 20);
+        });
+    }
+
+    #[test]
+    fn adopt_kitty_writes_nothing_when_the_fee_can_not_be_paid() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_pound(root(), Some(POUND)));
+            assert_ok!(Substratekitties::set_breed_fee(root(), 0, Some(TREASURY), false));
+            assert_ok!(Substratekitties::set_adoption_fee(root(), ENDOWMENT));
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::surrender_kitty(Origin::signed(ALICE), kitty_id));
+
+            assert_noop!(
+                Substratekitties::adopt_kitty(Origin::signed(BOB), kitty_id),
+                "Error: paying the fee would drop your balance below the existential deposit"
+            );
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(POUND));
+        });
+    }
+
+    #[test]
+    fn a_funded_loan_is_repaid_and_releases_the_collateral() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::request_loan(Origin::signed(ALICE), kitty_id, 100, 10, 5));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), Some(LockReason::Collateral));
+            assert!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_id).is_err());
+
+            assert_noop!(Substratekitties::fund_loan(Origin::signed(ALICE), kitty_id), "Error: you can not fund your own loan");
+            assert_ok!(Substratekitties::fund_loan(Origin::signed(BOB), kitty_id));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT + 100);
+            assert_eq!(Substratekitties::loan(kitty_id).unwrap().due, Some(5));
+            assert_noop!(Substratekitties::fund_loan(Origin::signed(TREASURY), kitty_id), "Error: this loan has already been funded");
+
+            assert_ok!(Substratekitties::repay_loan(Origin::signed(ALICE), kitty_id));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT - 10);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT + 10);
+            assert!(Substratekitties::loan(kitty_id).is_none());
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+        });
+    }
+
+    #[test]
+    fn fund_loan_moves_nothing_when_the_due_block_overflows() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::request_loan(Origin::signed(ALICE), kitty_id, 100, 10, u64::max_value()));
+            <system::Module<Test>>::set_block_number(1);
+
+            assert_noop!(Substratekitties::fund_loan(Origin::signed(BOB), kitty_id), Error::Overflow.as_str());
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT);
+        });
+    }
+
+    #[test]
+    fn the_lender_claims_the_collateral_after_the_grace_period() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::set_loan_grace_period(root(), 2));
+            assert_ok!(Substratekitties::request_loan(Origin::signed(ALICE), kitty_id, 100, 10, 5));
+            assert_ok!(Substratekitties::fund_loan(Origin::signed(BOB), kitty_id));
+
+            <system::Module<Test>>::set_block_number(7);
+            assert_noop!(
+                Substratekitties::claim_collateral(Origin::signed(BOB), kitty_id),
+                "Error: this loan is not past its due block and grace period yet"
+            );
+
+            <system::Module<Test>>::set_block_number(8);
+            assert_noop!(Substratekitties::repay_loan(Origin::signed(ALICE), kitty_id), "Error: this loan is past its due block");
+            assert_ok!(Substratekitties::claim_collateral(Origin::signed(BOB), kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert!(Substratekitties::loan(kitty_id).is_none());
+        });
+    }
+
+    #[test]
+    fn impound_counts_skipped_kitties_toward_the_batch_and_resumes_after_them() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_pound(root(), Some(POUND)));
+            for i in 0..MAX_IMPOUND_BATCH + 1 {
+                let kitty_id = H256::repeat_byte(i as u8 + 1);
+                assert_ok!(<Substratekitties as NonFungible>::mint(ALICE, kitty_id, kitty_id));
+                if i < MAX_IMPOUND_BATCH {
+                    <KittyLocks<Test>>::insert(kitty_id, LockReason::Auction);
+                }
+            }
+            let last = H256::repeat_byte(MAX_IMPOUND_BATCH as u8 + 1);
+
+            // 先頭の`MAX_IMPOUND_BATCH`匹はロック中なので、最初の引き取りでは1匹も移らない。
+            Balances::set_free_balance(&ALICE, 0);
+            assert!(Substratekitties::is_reaped(&ALICE));
+            assert_eq!(Substratekitties::owner_of(last), Some(ALICE));
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), MAX_IMPOUND_BATCH + 1);
+
+            // 飛ばしたkittyは末尾へ回ったので、次の呼び出しは残りのkittyから始まる。
+            assert_ok!(Substratekitties::rescue_kitties(Origin::signed(BOB), ALICE));
+            assert_eq!(Substratekitties::owner_of(last), Some(POUND));
+            assert!(Substratekitties::is_adoptable(last));
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), MAX_IMPOUND_BATCH);
+            assert!(Substratekitties::is_reaped(&ALICE));
+            assert_ok!(Substratekitties::verify_invariants());
+        });
+    }
+
+    #[test]
+    fn impound_moves_kitties_whose_deposit_is_gone_without_the_deposit() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_pound(root(), Some(POUND)));
+            assert_ok!(Substratekitties::set_kitty_deposit(root(), 10));
+            let kitty_id = create(ALICE);
+            assert_eq!(Substratekitties::deposit_of(kitty_id), 10);
+
+            // 確保分が先に消え、その後に口座が消される。
+            Balances::set_reserved_balance(&ALICE, 0);
+            Balances::set_free_balance(&ALICE, 0);
+
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(POUND));
+            assert!(Substratekitties::is_adoptable(kitty_id));
+            assert_eq!(Substratekitties::deposit_of(kitty_id), 0);
+            assert_eq!(Balances::reserved_balance(&POUND), 0);
+            assert!(!Substratekitties::is_reaped(&ALICE));
+            assert_ok!(Substratekitties::verify_invariants());
+        });
+    }
+
+    #[test]
+    fn transfer_all_with_no_kitties_moves_nothing() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::transfer_all(Origin::signed(ALICE), BOB));
+            assert_eq!(Substratekitties::owned_kitty_count(&BOB), 0);
+        });
+    }
+
+    #[test]
+    fn transfer_all_moves_one_and_many_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::transfer_all(Origin::signed(ALICE), BOB));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+
+            assert_ok!(Substratekitties::create_kitties(Origin::signed(ALICE), 5));
+            assert_ok!(Substratekitties::transfer_all(Origin::signed(ALICE), BOB));
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), 0);
+            assert_eq!(Substratekitties::owned_kitty_count(&BOB), 6);
+            assert_ok!(Substratekitties::verify_invariants());
+        });
+    }
+
+    #[test]
+    fn transfer_all_skips_locked_kitties_and_fails_only_when_none_can_move() {
+        with_externalities(&mut new_test_ext(), || {
+            let locked = create(ALICE);
+            let free = create(ALICE);
+            <KittyLocks<Test>>::insert(locked, LockReason::Auction);
+
+            assert_ok!(Substratekitties::transfer_all(Origin::signed(ALICE), BOB));
+            assert_eq!(Substratekitties::owner_of(free), Some(BOB));
+            assert_eq!(Substratekitties::owner_of(locked), Some(ALICE));
+
+            assert_noop!(Substratekitties::transfer_all(Origin::signed(ALICE), BOB), Error::NoMovableKitties.as_str());
+        });
+    }
+
+    #[test]
+    fn transfer_all_fails_when_the_recipient_would_exceed_the_cap() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_max_kitties_owned(root(), 2));
+            create(ALICE);
+            create(ALICE);
+            create(BOB);
+
+            assert_noop!(Substratekitties::transfer_all(Origin::signed(ALICE), BOB), Error::TooManyKittiesOwned.as_str());
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), 2);
+        });
+    }
+
+    #[test]
+    fn an_accepted_merge_moves_every_kitty() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::create_kitties(Origin::signed(ALICE), 3));
+            create(BOB);
+
+            assert_ok!(Substratekitties::propose_merge(Origin::signed(ALICE), BOB));
+            assert_ok!(Substratekitties::accept_merge(Origin::signed(BOB), ALICE));
+            assert_eq!(Substratekitties::owned_kitty_count(&ALICE), 0);
+            assert_eq!(Substratekitties::owned_kitty_count(&BOB), 4);
+            assert_eq!(Substratekitties::merge_proposal(&ALICE), None);
+            assert_ok!(Substratekitties::verify_invariants());
+        });
+    }
+
+    #[test]
+    fn accept_merge_needs_the_consent_of_both_sides() {
+        with_externalities(&mut new_test_ext(), || {
+            create(ALICE);
+            assert_noop!(Substratekitties::accept_merge(Origin::signed(BOB), ALICE), "Error: this account has not proposed a merge");
+
+            assert_ok!(Substratekitties::propose_merge(Origin::signed(ALICE), BOB));
+            assert_noop!(Substratekitties::accept_merge(Origin::signed(TREASURY), ALICE), "Error: this merge proposal is not addressed to you");
+        });
+    }
+
+    #[test]
+    fn accept_merge_leaves_locked_kitties_and_the_proposal_behind() {
+        with_externalities(&mut new_test_ext(), || {
+            let locked = create(ALICE);
+            let free = create(ALICE);
+            <KittyLocks<Test>>::insert(locked, LockReason::Collateral);
+            assert_ok!(Substratekitties::propose_merge(Origin::signed(ALICE), BOB));
+
+            assert_ok!(Substratekitties::accept_merge(Origin::signed(BOB), ALICE));
+            assert_eq!(Substratekitties::owner_of(free), Some(BOB));
+            assert_eq!(Substratekitties::owner_of(locked), Some(ALICE));
+            assert_eq!(Substratekitties::merge_proposal(&ALICE), Some(BOB));
+            assert_noop!(Substratekitties::accept_merge(Origin::signed(BOB), ALICE), Error::NoMovableKitties.as_str());
+
+            <KittyLocks<Test>>::remove(locked);
+            assert_ok!(Substratekitties::accept_merge(Origin::signed(BOB), ALICE));
+            assert_eq!(Substratekitties::owned_kitty_count(&BOB), 2);
+            assert_eq!(Substratekitties::merge_proposal(&ALICE), None);
+        });
+    }
+
+    #[test]
+    fn gift_random_only_probes_the_first_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            for i in 0..MAX_GIFT_PROBE + 1 {
+                let kitty_id = H256::repeat_byte(i as u8 + 1);
+                assert_ok!(<Substratekitties as NonFungible>::mint(ALICE, kitty_id, kitty_id));
+                if i < MAX_GIFT_PROBE {
+                    <KittyLocks<Test>>::insert(kitty_id, LockReason::Auction);
+                }
+            }
+
+            assert_noop!(Substratekitties::gift_random(Origin::signed(ALICE), BOB), "Error: all of your kitties are leased or locked");
+
+            <KittyLocks<Test>>::remove(H256::repeat_byte(MAX_GIFT_PROBE as u8));
+            assert_ok!(Substratekitties::gift_random(Origin::signed(ALICE), BOB));
+            assert_eq!(Substratekitties::owner_of(H256::repeat_byte(MAX_GIFT_PROBE as u8)), Some(BOB));
+        });
+    }
+
+    const FIGHTER: H256 = H256([0xff; 32]);
+    const DEFENDER: H256 = H256([0x01; 32]);
+
+    // ALICEに`challenger_dna`のDNAの`FIGHTER`を、BOBに`defender_dna`のDNAの`DEFENDER`を持たせる。
+    fn battle_kitties(challenger_dna: H256, defender_dna: H256) {
+        assert_ok!(<Substratekitties as NonFungible>::mint(ALICE, FIGHTER, challenger_dna));
+        assert_ok!(<Substratekitties as NonFungible>::mint(BOB, DEFENDER, defender_dna));
+    }
+
+    // `RANDOM_SEED`を`seed`にしたときの`FIGHTER`と`DEFENDER`の得点を、モジュールと同じ手順で求める。
+    fn expected_scores(seed: u64, challenger_dna: H256, defender_dna: H256) -> (u32, u32) {
+        RANDOM_SEED.with(|random_seed| *random_seed.borrow_mut() = seed);
+        let battle_seed = (TestRandomness::random(b"battle"), FIGHTER, DEFENDER).using_encoded(BlakeTwo256::hash);
+        Substratekitties::battle_scores(challenger_dna.as_ref(), defender_dna.as_ref(), battle_seed.as_ref())
+    }
+
+    #[test]
+    fn battle_scores_are_a_pure_function_of_the_dna_and_the_seed() {
+        assert_eq!(Substratekitties::battle_scores(&[0xff; 4], &[0x00; 4], &[0x00; 4]), (4, 0));
+        assert_eq!(Substratekitties::battle_scores(&[0xff; 4], &[0x00; 4], &[0xff; 4]), (0, 4));
+        assert_eq!(Substratekitties::battle_scores(&[0x0f, 0xf0], &[0xf0, 0x0f], &[0x00, 0x00]), (1, 1));
+        assert_eq!(Substratekitties::battle_scores(&[0x42; 4], &[0x42; 4], &[0x17; 4]), (0, 0));
+        assert_eq!(Substratekitties::battle_scores(&[0xff; 4], &[0x00; 2], &[0x00; 4]), (2, 0));
+    }
+
+    #[test]
+    fn the_battle_is_resolved_at_the_end_of_the_next_block_and_either_side_can_win() {
+        let (challenger_dna, defender_dna) = (H256([0xf0; 32]), H256([0x0f; 32]));
+        let challenger_wins = (0..100).find(|seed| {
+            let (c, d) = expected_scores(*seed, challenger_dna, defender_dna);
+            c > d
+        }).unwrap();
+        let defender_wins = (0..100).find(|seed| {
+            let (c, d) = expected_scores(*seed, challenger_dna, defender_dna);
+            d > c
+        }).unwrap();
+
+        for &(seed, winner, loser) in [(challenger_wins, ALICE, BOB), (defender_wins, BOB, ALICE)].iter() {
+            with_externalities(&mut new_test_ext(), || {
+                battle_kitties(challenger_dna, defender_dna);
+                assert_ok!(Substratekitties::challenge(Origin::signed(ALICE), FIGHTER, DEFENDER, 100, 10));
+                assert_ok!(Substratekitties::accept_challenge(Origin::signed(BOB), FIGHTER));
+                assert_eq!(Balances::reserved_balance(&ALICE), 100);
+                assert_eq!(Balances::reserved_balance(&BOB), 100);
+                assert_eq!(Substratekitties::battles_resolving_at(1), vec![FIGHTER]);
+
+                RANDOM_SEED.with(|random_seed| *random_seed.borrow_mut() = seed);
+                <Substratekitties as OnFinalise<u64>>::on_finalise(1);
+                assert_eq!(Balances::free_balance(&winner), ENDOWMENT + 100);
+                assert_eq!(Balances::free_balance(&loser), ENDOWMENT - 100);
+                assert_eq!(Balances::reserved_balance(&ALICE), 0);
+                assert_eq!(Balances::reserved_balance(&BOB), 0);
+                assert!(Substratekitties::pending_challenge(FIGHTER).is_none());
+                assert_eq!(Substratekitties::owner_of(FIGHTER), Some(ALICE));
+                assert_eq!(Substratekitties::owner_of(DEFENDER), Some(BOB));
+            });
         }
+    }
 
-        // 転送されたkittyの所有者を更新する。
-        <KittyOwner<T>>::insert(&kitty_id, &to);
+    #[test]
+    fn a_tied_battle_refunds_both_sides() {
+        with_externalities(&mut new_test_ext(), || {
+            battle_kitties(H256([0x42; 32]), H256([0x42; 32]));
+            assert_ok!(Substratekitties::challenge(Origin::signed(ALICE), FIGHTER, DEFENDER, 100, 10));
+            assert_ok!(Substratekitties::accept_challenge(Origin::signed(BOB), FIGHTER));
 
-        // 転送されたkittyは所有者にとって何番目であるかが変更されたので更新する。
-        <OwnedKittiesIndex<T>>::insert(kitty_id, owned_kitty_count_to);
+            <Substratekitties as OnFinalise<u64>>::on_finalise(1);
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT);
+            assert_eq!(Balances::reserved_balance(&ALICE), 0);
+            assert_eq!(Balances::reserved_balance(&BOB), 0);
+            assert!(Substratekitties::pending_challenge(FIGHTER).is_none());
+        });
+    }
 
-        // 転送した側は転送して個体数が減ったので更新する。\
-        <OwnedKittiesArray<T>>::remove((from.clone(), new_owned_kitty_count_from));
+    #[test]
+    fn an_expired_challenge_can_not_be_accepted_and_anyone_can_cancel_it() {
+        with_externalities(&mut new_test_ext(), || {
+            battle_kitties(FIGHTER, DEFENDER);
+            assert_ok!(Substratekitties::challenge(Origin::signed(ALICE), FIGHTER, DEFENDER, 100, 5));
+            assert_noop!(Substratekitties::cancel_challenge(Origin::signed(BOB), FIGHTER), "Error: you did not issue this challenge");
 
-        // 転送された側は持っている個体数が増えたので更新する。
-        // `to`の`owned_kitty_count_to`匹目が`kitty_id`という意味である。
-        <OwnedKittiesArray<T>>::insert((to.clone(), owned_kitty_count_to), kitty_id);
+            <system::Module<Test>>::set_block_number(6);
+            assert_noop!(Substratekitties::accept_challenge(Origin::signed(BOB), FIGHTER), "Error: this challenge has expired");
+            assert_ok!(Substratekitties::cancel_challenge(Origin::signed(BOB), FIGHTER));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT);
+            assert_eq!(Balances::reserved_balance(&ALICE), 0);
+            assert!(Substratekitties::pending_challenge(FIGHTER).is_none());
+        });
+    }
 
-        //  双方の持っている個体数を更新する。
-        <OwnedKittiesCount<T>>::insert(&from, new_owned_kitty_count_from);
-        <OwnedKittiesCount<T>>::insert(&to, new_owned_kitty_count_to);
+    #[test]
+    fn an_accepted_challenge_can_not_be_cancelled() {
+        with_externalities(&mut new_test_ext(), || {
+            battle_kitties(FIGHTER, DEFENDER);
+            assert_ok!(Substratekitties::challenge(Origin::signed(ALICE), FIGHTER, DEFENDER, 100, 5));
+            assert_ok!(Substratekitties::accept_challenge(Origin::signed(BOB), FIGHTER));
 
-        // Transferredイベントを吐く。
-        Self::deposit_event(RawEvent::Transferred(from, to, kitty_id));
+            assert_noop!(Substratekitties::cancel_challenge(Origin::signed(ALICE), FIGHTER), "Error: this challenge has already been accepted");
+            assert_noop!(Substratekitties::accept_challenge(Origin::signed(BOB), FIGHTER), "Error: this challenge has already been accepted");
+        });
+    }
 
-        Ok(())
+    #[test]
+    fn accept_challenge_fails_when_the_wager_can_not_be_matched() {
+        with_externalities(&mut new_test_ext(), || {
+            battle_kitties(FIGHTER, DEFENDER);
+            assert_ok!(Substratekitties::challenge(Origin::signed(ALICE), FIGHTER, DEFENDER, ENDOWMENT - 1, 5));
+            assert_ok!(Balances::make_transfer(&BOB, &TREASURY, 1));
+
+            assert_noop!(Substratekitties::accept_challenge(Origin::signed(BOB), FIGHTER), "Error: you do not have enough free balance to match the wager");
+        });
+    }
+
+    #[test]
+    fn the_breeding_cooldown_grows_with_congestion_and_saturates() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_breeding_cooldown(root(), 10, 100, 2));
+            assert_eq!(Substratekitties::current_breeding_cooldown(), 10);
+
+            <RecentBreedings<Test>>::put(4);
+            assert_eq!(Substratekitties::current_breeding_cooldown(), 30);
+
+            assert_ok!(Substratekitties::set_breeding_cooldown(root(), u64::max_value() / 2, 100, 2));
+            assert_eq!(Substratekitties::current_breeding_cooldown(), u64::max_value());
+        });
+    }
+
+    #[test]
+    fn purchases_accumulate_the_total_volume() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_1, 100, None));
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_2, 250, None));
+
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_1, 100));
+            assert_eq!(Substratekitties::total_volume(), 100);
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_2, 250));
+            assert_eq!(Substratekitties::total_volume(), 350);
+        });
+    }
+
+    #[test]
+    fn the_deposit_moves_with_the_kitty_and_needs_an_existing_recipient() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_kitty_deposit(root(), 10));
+            let kitty_id = create(ALICE);
+
+            assert_noop!(Substratekitties::transfer(Origin::signed(ALICE), 99, kitty_id), Error::NoDepositAccount.as_str());
+            assert_eq!(Balances::reserved_balance(&ALICE), 10);
+
+            assert_ok!(Substratekitties::transfer(Origin::signed(ALICE), BOB, kitty_id));
+            assert_eq!(Balances::reserved_balance(&ALICE), 0);
+            assert_eq!(Balances::reserved_balance(&BOB), 10);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT);
+        });
+    }
+
+    #[test]
+    fn an_auction_is_settled_in_on_finalise_at_its_end_block() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, None));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), Some(LockReason::Auction));
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 50));
+
+            <Substratekitties as OnFinalise<u64>>::on_finalise(4);
+            assert!(Substratekitties::auction(kitty_id).is_some());
+
+            <Substratekitties as OnFinalise<u64>>::on_finalise(5);
+            assert!(Substratekitties::auction(kitty_id).is_none());
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT + 50);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT - 50);
+            assert_eq!(Balances::reserved_balance(&BOB), 0);
+            assert_eq!(Substratekitties::active_auctions(&ALICE), 0);
+        });
+    }
+
+    #[test]
+    fn an_auction_without_bids_ends_in_on_finalise_and_unlocks_the_kitty() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, None));
+
+            <Substratekitties as OnFinalise<u64>>::on_finalise(5);
+            assert!(Substratekitties::auction(kitty_id).is_none());
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(ALICE));
+        });
+    }
+
+    #[test]
+    fn bids_are_reserved_and_returned_when_outbid() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, None));
+
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 50));
+            assert_eq!(Balances::reserved_balance(&BOB), 50);
+
+            // 自分の入札を引き上げるときは差額だけを確保する。
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 70));
+            assert_eq!(Balances::reserved_balance(&BOB), 70);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT - 70);
+
+            assert_noop!(Substratekitties::bid(Origin::signed(TREASURY), kitty_id, 70), "Error: your bid is not higher than the current highest bid");
+            assert_ok!(Substratekitties::bid(Origin::signed(TREASURY), kitty_id, 80));
+            assert_eq!(Balances::reserved_balance(&BOB), 0);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT);
+            assert_eq!(Balances::reserved_balance(&TREASURY), 80);
+        });
+    }
+
+    #[test]
+    fn a_bid_can_not_exceed_the_free_balance() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, None));
+
+            assert_noop!(Substratekitties::bid(Origin::signed(BOB), kitty_id, ENDOWMENT + 1), "Error: you do not have enough free balance for this bid");
+            assert_noop!(Substratekitties::bid(Origin::signed(ALICE), kitty_id, 50), "Error: you can not bid on your own kitty");
+        });
+    }
+
+    #[test]
+    fn an_auction_below_its_reserve_price_returns_the_bid_and_keeps_the_kitty() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 100, false, None));
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 50));
+
+            <Substratekitties as OnFinalise<u64>>::on_finalise(5);
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(ALICE));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT);
+            assert_eq!(Balances::reserved_balance(&BOB), 0);
+        });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn an_auction_can_reject_bids_below_its_reserve_price() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 100, true, None));
+
+            assert_noop!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 50), "Error: your bid is below the reserve price");
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 100));
+
+            <Substratekitties as OnFinalise<u64>>::on_finalise(5);
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+        });
+    }
+
+    #[test]
+    fn buy_now_ends_the_auction_and_returns_the_highest_bid() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_noop!(
+                Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, Some(5)),
+                "Error: the buy-now price must not be below the minimum bid"
+            );
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, Some(200)));
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 50));
+
+            assert_ok!(Substratekitties::buy_now(Origin::signed(TREASURY), kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(TREASURY));
+            assert!(Substratekitties::auction(kitty_id).is_none());
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT + 200);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT - 200);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT);
+            assert_eq!(Balances::reserved_balance(&BOB), 0);
+        });
+    }
+
+    #[test]
+    fn buy_now_is_closed_once_a_bid_reaches_the_price() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, Some(200)));
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 200));
+
+            assert_noop!(Substratekitties::buy_now(Origin::signed(TREASURY), kitty_id), "Error: a bid has already reached the buy-now price");
+        });
+    }
+
+    #[test]
+    fn a_late_bid_extends_the_auction_up_to_the_maximum_number_of_times() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_auction_extension(root(), 3, 1));
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, None));
+
+            <system::Module<Test>>::set_block_number(3);
+            assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, 50));
+            assert_eq!(Substratekitties::auction(kitty_id).unwrap().end_block, 6);
+            assert_eq!(Substratekitties::auction_extensions(kitty_id), 1);
+
+            <system::Module<Test>>::set_block_number(5);
+            assert_ok!(Substratekitties::bid(Origin::signed(TREASURY), kitty_id, 60));
+            assert_eq!(Substratekitties::auction(kitty_id).unwrap().end_block, 6);
+
+            // 元の終了ブロックの予定は読み飛ばされる。
+            <Substratekitties as OnFinalise<u64>>::on_finalise(5);
+            assert!(Substratekitties::auction(kitty_id).is_some());
+
+            <system::Module<Test>>::set_block_number(6);
+            <Substratekitties as OnFinalise<u64>>::on_finalise(6);
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(TREASURY));
+        });
+    }
+
+    #[test]
+    fn the_bid_history_is_bounded_and_cleared_after_the_retention_period() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_bid_history_retention(root(), 2));
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_id, 10, 5, 0, false, None));
+
+            for amount in 10..(10 + MAX_BID_HISTORY as u64 + 1) {
+                assert_ok!(Substratekitties::bid(Origin::signed(BOB), kitty_id, amount));
+            }
+            let history = Substratekitties::bid_history(kitty_id);
+            assert_eq!(history.len(), MAX_BID_HISTORY);
+            assert_eq!(history[0], (BOB, 11, 0));
+
+            <system::Module<Test>>::set_block_number(5);
+            <Substratekitties as OnFinalise<u64>>::on_finalise(5);
+            assert_eq!(Substratekitties::bid_history(kitty_id).len(), MAX_BID_HISTORY);
+
+            <Substratekitties as OnFinalise<u64>>::on_finalise(7);
+            assert!(Substratekitties::bid_history(kitty_id).is_empty());
+        });
+    }
+
+    #[test]
+    fn an_account_can_run_at_most_the_maximum_number_of_auctions() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Substratekitties::set_max_auctions_per_account(root(), 1));
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_1, 10, 5, 0, false, None));
+
+            assert_noop!(
+                Substratekitties::start_auction(Origin::signed(ALICE), kitty_2, 10, 5, 0, false, None),
+                "Error: you are already running the maximum number of auctions"
+            );
+
+            assert_ok!(Substratekitties::cancel_auction(Origin::signed(ALICE), kitty_1));
+            assert_eq!(Substratekitties::active_auctions(&ALICE), 0);
+            assert_ok!(Substratekitties::start_auction(Origin::signed(ALICE), kitty_2, 10, 5, 0, false, None));
+        });
+    }
+
+    #[test]
+    fn buy_dutch_pays_the_current_price_and_honours_the_minimum_acceptable_price() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::start_dutch(Origin::signed(ALICE), kitty_id, 100, 10, 10));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), Some(LockReason::DutchAuction));
+
+            // 5ブロック後の価格は100から90の半分下がって55になる。
+            <system::Module<Test>>::set_block_number(5);
+            assert_noop!(
+                Substratekitties::buy_dutch(Origin::signed(BOB), kitty_id, 100, Some(60)),
+                "Error: the current dutch auction price is below your minimum acceptable price"
+            );
+            assert_noop!(Substratekitties::buy_dutch(Origin::signed(BOB), kitty_id, 50, None), Error::PriceTooHigh.as_str());
+
+            assert_ok!(Substratekitties::buy_dutch(Origin::signed(BOB), kitty_id, 100, Some(50)));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert!(Substratekitties::dutch_auction(kitty_id).is_none());
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT + 55);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT - 55);
+        });
+    }
+
+    #[test]
+    fn an_accepted_swap_exchanges_the_kitties() {
+        with_externalities(&mut new_test_ext(), || {
+            let alice_kitty = create(ALICE);
+            let bob_kitty = create(BOB);
+            assert_ok!(Substratekitties::propose_swap(Origin::signed(ALICE), alice_kitty, bob_kitty));
+
+            assert_noop!(Substratekitties::accept_swap(Origin::signed(TREASURY), alice_kitty, bob_kitty), "Error: this swap proposal is not addressed to you");
+            assert_ok!(Substratekitties::accept_swap(Origin::signed(BOB), alice_kitty, bob_kitty));
+            assert_eq!(Substratekitties::owner_of(alice_kitty), Some(BOB));
+            assert_eq!(Substratekitties::owner_of(bob_kitty), Some(ALICE));
+            assert!(Substratekitties::swap_proposal((alice_kitty, bob_kitty)).is_none());
+            assert_ok!(Substratekitties::verify_invariants());
+        });
+    }
+
+    #[test]
+    fn an_expired_or_cancelled_swap_can_not_be_accepted() {
+        with_externalities(&mut new_test_ext(), || {
+            let alice_kitty = create(ALICE);
+            let bob_kitty = create(BOB);
+            assert_ok!(Substratekitties::propose_swap(Origin::signed(ALICE), alice_kitty, bob_kitty));
+
+            <system::Module<Test>>::set_block_number(SWAP_PROPOSAL_PERIOD + 1);
+            assert_noop!(Substratekitties::accept_swap(Origin::signed(BOB), alice_kitty, bob_kitty), "Error: this swap proposal has expired");
+
+            assert_ok!(Substratekitties::cancel_swap(Origin::signed(ALICE), alice_kitty, bob_kitty));
+            assert_noop!(Substratekitties::accept_swap(Origin::signed(BOB), alice_kitty, bob_kitty), "Error: there is no such swap proposal");
+            assert_eq!(Substratekitties::owner_of(alice_kitty), Some(ALICE));
+        });
+    }
+
+    #[test]
+    fn a_lease_returns_the_kitty_to_the_lessor_at_expiry() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::lease_kitty(Origin::signed(ALICE), BOB, kitty_id, 5, 30));
+            assert_ok!(Substratekitties::accept_lease(Origin::signed(BOB), kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), Some(LockReason::Lease));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT + 30);
+            assert_eq!(Balances::free_balance(&BOB), ENDOWMENT - 30);
+
+            // 借り手は借りたkittyを転送も出品もできない。
+            assert!(Substratekitties::transfer(Origin::signed(BOB), TREASURY, kitty_id).is_err());
+            assert!(Substratekitties::set_price(Origin::signed(BOB), kitty_id, 100, None).is_err());
+
+            <Substratekitties as OnFinalise<u64>>::on_finalise(5);
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(ALICE));
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert!(Substratekitties::lease(kitty_id).is_none());
+            assert_ok!(Substratekitties::verify_invariants());
+        });
+    }
+
+    #[test]
+    fn a_lease_ends_early_only_when_both_parties_agree() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::lease_kitty(Origin::signed(ALICE), BOB, kitty_id, 5, 0));
+            assert_noop!(Substratekitties::accept_lease(Origin::signed(TREASURY), kitty_id), "Error: this lease offer is not addressed to you");
+            assert_ok!(Substratekitties::accept_lease(Origin::signed(BOB), kitty_id));
+
+            assert_ok!(Substratekitties::terminate_lease(Origin::signed(ALICE), kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(BOB));
+            assert_noop!(Substratekitties::reclaim_lease(Origin::signed(ALICE), kitty_id), "Error: this lease has not expired yet");
+
+            assert_ok!(Substratekitties::terminate_lease(Origin::signed(BOB), kitty_id));
+            assert_eq!(Substratekitties::owner_of(kitty_id), Some(ALICE));
+            assert!(Substratekitties::lease(kitty_id).is_none());
+        });
+    }
+
+    #[test]
+    fn a_burned_breed_fee_reduces_the_total_issuance() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            assert_noop!(
+                Substratekitties::set_breed_fee(root(), 10, None, false),
+                "Error: a breeding fee must be either burned or sent to a treasury"
+            );
+            assert_ok!(Substratekitties::set_breed_fee(root(), 10, None, true));
+
+            let issuance = Balances::total_issuance();
+            assert_ok!(Substratekitties::breed_kitty(Origin::signed(ALICE), kitty_1, kitty_2, None));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT - 10);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT);
+            assert_eq!(Balances::total_issuance(), issuance - 10);
+        });
+    }
+
+    #[test]
+    fn a_breed_fee_goes_to_the_treasury_when_it_is_not_burned() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_1 = create(ALICE);
+            let kitty_2 = create(ALICE);
+            assert_ok!(Substratekitties::set_breed_fee(root(), 10, Some(TREASURY), false));
+
+            let issuance = Balances::total_issuance();
+            assert_ok!(Substratekitties::breed_kitty(Origin::signed(ALICE), kitty_1, kitty_2, None));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT - 10);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT + 10);
+            assert_eq!(Balances::total_issuance(), issuance);
+        });
+    }
+
+    #[test]
+    fn highest_sale_only_records_a_price_above_the_previous_record() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_eq!(Substratekitties::highest_sale(), None);
+
+            <system::Module<Test>>::set_block_number(3);
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_id, 50, None));
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_id, 50));
+            assert_eq!(Substratekitties::highest_sale(), Some((kitty_id, BOB, ALICE, 50, 3)));
+
+            // 同額以下の売買では記録は変わらない。
+            <system::Module<Test>>::set_block_number(4);
+            assert_ok!(Substratekitties::set_price(Origin::signed(BOB), kitty_id, 50, None));
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(ALICE), kitty_id, 50));
+            assert_eq!(Substratekitties::highest_sale(), Some((kitty_id, BOB, ALICE, 50, 3)));
+
+            <system::Module<Test>>::set_block_number(5);
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_id, 60, None));
+            assert_ok!(Substratekitties::buy_kitty(Origin::signed(BOB), kitty_id, 60));
+            assert_eq!(Substratekitties::highest_sale(), Some((kitty_id, BOB, ALICE, 60, 5)));
+        });
+    }
+
+    #[test]
+    fn unstake_kitty_pays_a_reward_for_the_blocks_staked() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::set_staking_reward(root(), Some(TREASURY), 2));
+            assert_ok!(Substratekitties::set_price(Origin::signed(ALICE), kitty_id, 50, None));
+
+            <system::Module<Test>>::set_block_number(1);
+            assert_ok!(Substratekitties::stake_kitty(Origin::signed(ALICE), kitty_id));
+            assert_eq!(Substratekitties::price_of(kitty_id), 0);
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), Some(LockReason::Staked));
+            assert_eq!(Substratekitties::staked_count(&ALICE), 1);
+            assert_noop!(Substratekitties::unstake_kitty(Origin::signed(BOB), kitty_id), "Error: you did not stake this kitty");
+
+            // 第0世代のkittyを10ブロックステークしたので、報酬は2 * 10 / 1。
+            <system::Module<Test>>::set_block_number(11);
+            assert_ok!(Substratekitties::unstake_kitty(Origin::signed(ALICE), kitty_id));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT + 20);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT - 20);
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+            assert_eq!(Substratekitties::staked_count(&ALICE), 0);
+            assert!(Substratekitties::staked_kitty(kitty_id).is_none());
+        });
+    }
+
+    #[test]
+    fn unstake_kitty_still_releases_the_kitty_when_the_pot_can_not_pay() {
+        with_externalities(&mut new_test_ext(), || {
+            let kitty_id = create(ALICE);
+            assert_ok!(Substratekitties::set_staking_reward(root(), Some(TREASURY), ENDOWMENT));
+
+            <system::Module<Test>>::set_block_number(1);
+            assert_ok!(Substratekitties::stake_kitty(Origin::signed(ALICE), kitty_id));
+            <system::Module<Test>>::set_block_number(2);
+            assert_ok!(Substratekitties::unstake_kitty(Origin::signed(ALICE), kitty_id));
+            assert_eq!(Balances::free_balance(&ALICE), ENDOWMENT);
+            assert_eq!(Balances::free_balance(&TREASURY), ENDOWMENT);
+            assert_eq!(Substratekitties::kitty_lock(kitty_id), None);
+        });
+    }
+}
@@ -1,7 +1,7 @@
 use primitives::{Ed25519AuthorityId, ed25519};
 use node_template_runtime::{
 	AccountId, GenesisConfig, ConsensusConfig, TimestampConfig, BalancesConfig,
-	SudoConfig, IndicesConfig
+	SudoConfig, IndicesConfig, SubstratekittiesConfig
 };
 use substrate_service;
 
@@ -101,5 +101,11 @@ fn testnet_genesis(initial_authorities: Vec<Ed25519AuthorityId>, endowed_account
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
+		substratekitties: Some(SubstratekittiesConfig {
+			creation_fee: 0,
+			max_kitties_owned: 0,
+			create_cooldown: 0,
+			base_breeding_cooldown: 0,
+		}),
 	}
 }